@@ -0,0 +1,680 @@
+use darrentsung_debug_parser::{Term, Value};
+
+use crate::{parse, Migration};
+
+/// Assigns a rank to an enum variant name, lower ranks are ordered first. See
+/// [`SortConfig::variant_rank`].
+pub type VariantRankFn = Box<dyn Fn(&str) -> i64>;
+
+/// Decides whether a map entry is compared, called with its key and value. See
+/// [`SortConfig::filter_entries`].
+pub type EntryFilterFn = Box<dyn Fn(&Value, &Value) -> bool>;
+
+/// Rewrites a map key or struct field name, see [`SortConfig::key_transform`].
+pub type KeyTransformFn = Box<dyn Fn(&str) -> String>;
+
+/// Orders two map keys or set elements, see [`SortConfig::comparator`].
+pub type ComparatorFn = Box<dyn Fn(&Value, &Value) -> std::cmp::Ordering>;
+
+/// Checks an invariant of a sorted tree, returning a description of the violation as the
+/// error. See [`SortConfig::validate`].
+pub type ValidateFn = Box<dyn Fn(&Value) -> Result<(), String>>;
+
+/// Whether the collection in a struct field is sorted, see [`SortConfig::schema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// The order of the list or tuple is preserved.
+    Ordered,
+    /// The list or tuple is sorted.
+    Unordered,
+}
+
+/// Options controlling how Debug output is normalized and sorted before it's compared.
+///
+/// The default configuration matches the behavior of [`assert_eq_sorted`](crate::assert_eq_sorted).
+/// Options are set with builder-style methods:
+///
+/// ```rust
+/// use pretty_assertions_sorted::SortConfig;
+///
+/// let config = SortConfig::new().keys_only(true);
+/// ```
+pub struct SortConfig {
+    pub(crate) keys_only: bool,
+    pub(crate) sort_sequences: bool,
+    pub(crate) max_input_bytes: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) detect_moves: bool,
+    pub(crate) variant_rank: Option<VariantRankFn>,
+    pub(crate) max_term_len: Option<usize>,
+    pub(crate) case_insensitive_keys: bool,
+    pub(crate) natural_order: bool,
+    pub(crate) lenient: bool,
+    pub(crate) sort_struct_fields: bool,
+    pub(crate) trim_terms: bool,
+    pub(crate) redact_timestamps: bool,
+    pub(crate) ignore_list_indices: Vec<usize>,
+    pub(crate) report_original_positions: bool,
+    pub(crate) structured_panic: bool,
+    pub(crate) first_diff_only: bool,
+    pub(crate) field_order: Vec<String>,
+    pub(crate) default_for_missing: Option<Value>,
+    pub(crate) filter_entries: Option<EntryFilterFn>,
+    pub(crate) verbatim_values: bool,
+    pub(crate) locale_floats: bool,
+    pub(crate) max_output_lines: Option<usize>,
+    pub(crate) variant_only: bool,
+    pub(crate) bytes_as_string: bool,
+    pub(crate) sort_tuples: bool,
+    pub(crate) ignore_fields: Vec<String>,
+    pub(crate) unify_numbers: bool,
+    pub(crate) unified_diff: bool,
+    pub(crate) canonical_keys: bool,
+    pub(crate) migrations: Vec<Migration>,
+    pub(crate) unwrap_options: bool,
+    pub(crate) pair_list_elements: bool,
+    pub(crate) schema: Vec<(String, FieldKind)>,
+    pub(crate) group_by_top_key: bool,
+    pub(crate) unordered_only: Vec<String>,
+    pub(crate) float_tolerance: Option<f64>,
+    pub(crate) transparent_wrappers: Vec<String>,
+    pub(crate) summary: bool,
+    pub(crate) treat_signed_zero_equal: bool,
+    pub(crate) treat_pair_lists_as_maps: bool,
+    pub(crate) unwrap_cells: bool,
+    pub(crate) key_transform: Option<KeyTransformFn>,
+    pub(crate) side_by_side: bool,
+    pub(crate) exact: bool,
+    pub(crate) validate: Option<ValidateFn>,
+    pub(crate) blocklist: Vec<String>,
+    pub(crate) max_differences: Option<usize>,
+    pub(crate) max_sort_depth: usize,
+    pub(crate) warn_max_sort_depth: bool,
+    pub(crate) comparator: Option<ComparatorFn>,
+    pub(crate) sort_by_value: bool,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        Self {
+            keys_only: false,
+            sort_sequences: false,
+            max_input_bytes: None,
+            max_depth: None,
+            detect_moves: false,
+            variant_rank: None,
+            max_term_len: None,
+            case_insensitive_keys: false,
+            natural_order: false,
+            lenient: false,
+            sort_struct_fields: false,
+            trim_terms: false,
+            redact_timestamps: false,
+            ignore_list_indices: Vec::new(),
+            report_original_positions: false,
+            structured_panic: false,
+            first_diff_only: false,
+            field_order: Vec::new(),
+            default_for_missing: None,
+            filter_entries: None,
+            verbatim_values: false,
+            locale_floats: false,
+            max_output_lines: None,
+            variant_only: false,
+            bytes_as_string: false,
+            sort_tuples: false,
+            ignore_fields: Vec::new(),
+            unify_numbers: false,
+            unified_diff: false,
+            canonical_keys: false,
+            migrations: Vec::new(),
+            unwrap_options: false,
+            pair_list_elements: false,
+            schema: Vec::new(),
+            group_by_top_key: false,
+            unordered_only: Vec::new(),
+            float_tolerance: None,
+            transparent_wrappers: Vec::new(),
+            summary: false,
+            treat_signed_zero_equal: false,
+            treat_pair_lists_as_maps: false,
+            unwrap_cells: true,
+            key_transform: None,
+            side_by_side: false,
+            exact: false,
+            validate: None,
+            blocklist: Vec::new(),
+            max_differences: None,
+            max_sort_depth: parse::DEFAULT_MAX_DEPTH,
+            warn_max_sort_depth: false,
+            comparator: None,
+            sort_by_value: false,
+        }
+    }
+}
+
+impl SortConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The default behavior of [`assert_eq_sorted`](crate::assert_eq_sorted): only maps and
+    /// sets are sorted, lists, tuples and everything else are compared exactly.
+    pub fn strict() -> Self {
+        Self::default()
+    }
+
+    /// Disables all sorting and normalization, so the unsorted Debug output is compared
+    /// and reported exactly like [`pretty_assertions::assert_eq`] does. Useful as a
+    /// per-assertion override when sorting might hide a real ordering bug.
+    pub fn exact() -> Self {
+        Self {
+            exact: true,
+            ..Self::default()
+        }
+    }
+
+    /// For data that originates from JSON-like sources: keys are ordered case-insensitively
+    /// and naturally, and values that can't be parsed fall back to unsorted output.
+    pub fn json_like() -> Self {
+        Self::default()
+            .case_insensitive_keys(true)
+            .natural_order(true)
+            .lenient(true)
+    }
+
+    /// For snapshot tests: struct fields are sorted and surrounding whitespace in string
+    /// terms is ignored, so output is stable across refactors that reorder fields.
+    pub fn snapshot() -> Self {
+        Self::default().sort_struct_fields(true).trim_terms(true)
+    }
+
+    /// Only compare the keys of maps, ignoring their values entirely. Each map is reduced
+    /// to the set of its keys so the diff only shows added / removed keys.
+    pub fn keys_only(mut self, keys_only: bool) -> Self {
+        self.keys_only = keys_only;
+        self
+    }
+
+    /// Whether lists and tuples are sorted (default: `false`). Their order is often
+    /// meaningful (eg. a log of events), so by default it's preserved and only maps and sets
+    /// nested inside them are sorted. Enable this to also compare lists and tuples
+    /// regardless of order.
+    ///
+    /// Maps and sets (eg. `HashMap` / `HashSet`, which render as `{k: v}` / `{a, b}`) have no
+    /// meaningful order and are always sorted. Lists and tuples (eg. `Vec`, which renders as
+    /// `[a, b]`) are the only collections affected by this option.
+    pub fn sort_sequences(mut self, sort_sequences: bool) -> Self {
+        self.sort_sequences = sort_sequences;
+        self
+    }
+
+    /// Fail with [`SortError::InputTooLarge`](crate::SortError::InputTooLarge) when the Debug
+    /// output is longer than `max_input_bytes`.
+    ///
+    /// The Debug output is cut off as soon as it exceeds the limit, which guards against
+    /// cyclic or runaway Debug implementations.
+    pub fn max_input_bytes(mut self, max_input_bytes: usize) -> Self {
+        self.max_input_bytes = Some(max_input_bytes);
+        self
+    }
+
+    /// Fail with [`SortError::DepthExceeded`](crate::SortError::DepthExceeded) when the Debug
+    /// output is nested more than `max_depth` levels deep.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// When a comparison fails, list the elements of lists and tuples that appear on both
+    /// sides but at a different position (eg. `.log[0] -> .log[2]: "a"`). Not useful
+    /// together with [`sort_sequences(true)`](Self::sort_sequences), since sorted lists
+    /// never contain moves.
+    pub fn detect_moves(mut self, detect_moves: bool) -> Self {
+        self.detect_moves = detect_moves;
+        self
+    }
+
+    /// Orders enum variants (identifier terms, and the names of struct and tuple variants)
+    /// by the rank returned from `variant_rank`, lowest first, instead of alphabetically.
+    /// Variants with the same rank are ordered as usual.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::SortConfig;
+    ///
+    /// let config = SortConfig::new().variant_rank(Box::new(|variant| match variant {
+    ///     "Error" => 0,
+    ///     "Warn" => 1,
+    ///     _ => 2,
+    /// }));
+    /// ```
+    pub fn variant_rank(mut self, variant_rank: VariantRankFn) -> Self {
+        self.variant_rank = Some(variant_rank);
+        self
+    }
+
+    /// Abbreviates string terms longer than `max_term_len` characters to
+    /// `"prefix...[truncated N chars]"` when displayed. Comparisons still use the full
+    /// content.
+    pub fn max_term_len(mut self, max_term_len: usize) -> Self {
+        self.max_term_len = Some(max_term_len);
+        self
+    }
+
+    /// Order string terms (eg. `String` map keys and set elements) ignoring ASCII / Unicode
+    /// case, while keeping their original casing in the output. Strings that only differ in
+    /// case are ordered case-sensitively so the output stays deterministic.
+    pub fn case_insensitive_keys(mut self, case_insensitive_keys: bool) -> Self {
+        self.case_insensitive_keys = case_insensitive_keys;
+        self
+    }
+
+    /// Order terms "naturally" by comparing runs of digits numerically, eg. `"item2"` sorts
+    /// before `"item10"`. Leading zeros don't affect the order but are kept in the output,
+    /// eg. `"7"` sorts before `"042"`.
+    pub fn natural_order(mut self, natural_order: bool) -> Self {
+        self.natural_order = natural_order;
+        self
+    }
+
+    /// Fall back to the unsorted `{:#?}` output instead of panicking when the Debug output
+    /// can't be parsed.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sort the fields of structs, including struct variants of enums, by name. A
+    /// non-exhaustive `..` marker stays last.
+    pub fn sort_struct_fields(mut self, sort_struct_fields: bool) -> Self {
+        self.sort_struct_fields = sort_struct_fields;
+        self
+    }
+
+    /// Strip leading and trailing whitespace from string terms before they're compared and
+    /// displayed.
+    pub fn trim_terms(mut self, trim_terms: bool) -> Self {
+        self.trim_terms = trim_terms;
+        self
+    }
+
+    /// Replace `SystemTime` and `Instant` values with a `<redacted>` placeholder so structures
+    /// containing timestamps can be compared.
+    ///
+    /// These types render differently on each platform (eg. `SystemTime { tv_sec: .., tv_nsec:
+    /// .. }` on Unix and `SystemTime { intervals: .. }` on Windows), so they're matched by
+    /// type name only.
+    pub fn redact_timestamps(mut self, redact_timestamps: bool) -> Self {
+        self.redact_timestamps = redact_timestamps;
+        self
+    }
+
+    /// Replace the elements at `indices` of every list with an `<ignored>` placeholder before
+    /// comparing, eg. for lists where the first element is generated. Indices refer to the
+    /// original (unsorted) order.
+    pub fn ignore_list_indices(mut self, indices: &[usize]) -> Self {
+        self.ignore_list_indices = indices.to_vec();
+        self
+    }
+
+    /// When a comparison fails, list the list elements that only appear on one side along
+    /// with their index in the original (unsorted) Debug output, eg. `left.items[2]: "c"`.
+    pub fn report_original_positions(mut self, report_original_positions: bool) -> Self {
+        self.report_original_positions = report_original_positions;
+        self
+    }
+
+    /// Make the assertion macros panic with a [`SortAssertionError`](crate::SortAssertionError)
+    /// payload instead of a formatted string, so that panic hooks can downcast it and
+    /// inspect the sorted sides and the diff.
+    ///
+    /// Note that `#[should_panic(expected = "...")]` only matches string payloads.
+    pub fn structured_panic(mut self, structured_panic: bool) -> Self {
+        self.structured_panic = structured_panic;
+        self
+    }
+
+    /// When a comparison fails, only report the first path at which the sorted values
+    /// differ instead of rendering a diff of the whole Debug output. This is much cheaper
+    /// for very large values.
+    pub fn first_diff_only(mut self, first_diff_only: bool) -> Self {
+        self.first_diff_only = first_diff_only;
+        self
+    }
+
+    /// Display struct fields in the given order, eg. `&["id", "name"]`. Fields that aren't
+    /// listed are sorted alphabetically after the listed ones, as with
+    /// [`sort_struct_fields`](Self::sort_struct_fields).
+    pub fn field_order(mut self, fields: &[&str]) -> Self {
+        self.field_order = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+
+    /// When comparing two maps where only one side has a key, compare the other side as if
+    /// it had the key with `default` as its value, eg. `Term::Ident("None".to_string())`
+    /// for optional values or `Term::UnquotedRawString("0".to_string())` for counters.
+    ///
+    /// The diff then shows the value of such a key against `default`.
+    pub fn default_for_missing(mut self, default: Term) -> Self {
+        self.default_for_missing = Some(Value::Term(default));
+        self
+    }
+
+    /// Only compare the map entries for which `filter_entries(key, value)` returns `true`,
+    /// other entries are dropped from both sides before they're compared.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::{SortConfig, Term, Value};
+    ///
+    /// let config = SortConfig::new().filter_entries(Box::new(|key, _value| {
+    ///     matches!(key, Value::Term(Term::String(key)) if key.starts_with("user."))
+    /// }));
+    /// ```
+    pub fn filter_entries(mut self, filter_entries: EntryFilterFn) -> Self {
+        self.filter_entries = Some(filter_entries);
+        self
+    }
+
+    /// Keep map values exactly as formatted by their Debug implementation (on a single
+    /// line) instead of re-rendering them, while still sorting the keys. The values are only
+    /// scanned for their end instead of being parsed, which is useful for large values that
+    /// are expensive to parse and pretty-print.
+    ///
+    /// Values are compared as opaque text, so maps nested in them aren't sorted.
+    pub fn verbatim_values(mut self, verbatim_values: bool) -> Self {
+        self.verbatim_values = verbatim_values;
+        self
+    }
+
+    /// Accept floats with a comma as their decimal separator, eg. `1,5` from a custom Debug
+    /// implementation, and normalize them to `1.5`.
+    ///
+    /// Only a comma directly between two digits is treated as a decimal separator, Debug
+    /// output always follows its element separators with a space or a newline.
+    pub fn locale_floats(mut self, locale_floats: bool) -> Self {
+        self.locale_floats = locale_floats;
+        self
+    }
+
+    /// Cuts the displayed output of each side after `max_output_lines` lines, followed by a
+    /// `... (truncated)` line. Like [`max_term_len`](Self::max_term_len) this only affects
+    /// the display, values are still compared in full.
+    pub fn max_output_lines(mut self, max_output_lines: usize) -> Self {
+        self.max_output_lines = Some(max_output_lines);
+        self
+    }
+
+    /// Compare enum variants by name only, ignoring their payloads, eg. `Active { since: 1 }`
+    /// and `Active { since: 2 }` compare equal.
+    ///
+    /// Debug output doesn't distinguish enum variants from structs, so every struct and
+    /// named tuple (including eg. `Some(..)`) is reduced to its name.
+    pub fn variant_only(mut self, variant_only: bool) -> Self {
+        self.variant_only = variant_only;
+        self
+    }
+
+    /// Display byte arrays (lists of numbers in `0..=255`, eg. the Debug output of a
+    /// `Vec<u8>`) and byte string literals (eg. `b"hi"`) the same way, as byte strings, so
+    /// that both forms compare equal.
+    ///
+    /// Note that any list of small numbers, including empty lists, is displayed as a byte
+    /// string.
+    pub fn bytes_as_string(mut self, bytes_as_string: bool) -> Self {
+        self.bytes_as_string = bytes_as_string;
+        self
+    }
+
+    /// Order tuples element by element with numbers compared by their value, so that eg. a
+    /// set of `(i32, String)` sorts `(9, "b")` before `(10, "a")`. Without it, numbers in
+    /// tuples are compared as text like any other term.
+    pub fn sort_tuples(mut self, sort_tuples: bool) -> Self {
+        self.sort_tuples = sort_tuples;
+        self
+    }
+
+    /// Drop the struct fields with the given names before comparing, eg. volatile
+    /// `&["strong_count", "weak_count"]` fields of reference-counted wrappers.
+    pub fn ignore_fields(mut self, fields: &[&str]) -> Self {
+        self.ignore_fields = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+
+    /// Compare integers and floats with the same value as equal, eg. `42` and `42.0`, as
+    /// happens when data goes through a serialization format like JSON. Floats without a
+    /// fractional part are displayed as integers.
+    pub fn unify_numbers(mut self, unify_numbers: bool) -> Self {
+        self.unify_numbers = unify_numbers;
+        self
+    }
+
+    /// Display differences as a unified diff (like `diff -u`, with `-` / `+` line prefixes
+    /// and 3 lines of context) instead of the colored diff of `pretty_assertions`.
+    pub fn unified_diff(mut self, unified_diff: bool) -> Self {
+        self.unified_diff = unified_diff;
+        self
+    }
+
+    /// Lowercase and trim the string and identifier keys of maps before they're compared
+    /// and sorted, so that eg. `" Name "` and `"name"` match.
+    pub fn canonical_keys(mut self, canonical_keys: bool) -> Self {
+        self.canonical_keys = canonical_keys;
+        self
+    }
+
+    /// Apply `migrations` (in order) to the parsed Debug output of both sides before
+    /// anything else, eg. to compare golden output of an older version of a dependency
+    /// against a newer one. Combine with [`sort_struct_fields`](Self::sort_struct_fields)
+    /// to also ignore reordered fields.
+    pub fn migrations(mut self, migrations: Vec<Migration>) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    /// Replace `Some(x)` with `x` on both sides, so that eg. `Some(5)` and `5` compare
+    /// equal.
+    ///
+    /// `None` is kept as-is, which makes it ambiguous: `Some(None)` and `None` compare equal,
+    /// as do `Some(None)` and a unit variant named `None` of another enum.
+    pub fn unwrap_options(mut self, unwrap_options: bool) -> Self {
+        self.unwrap_options = unwrap_options;
+        self
+    }
+
+    /// When a comparison fails, pair up the elements of lists and tuples by position and
+    /// list every differing field of them (eg. `[1].name: "a" != "b"`), instead of only
+    /// showing the changed elements in the line diff. Not useful together with
+    /// [`sort_sequences(true)`](Self::sort_sequences), since sorting pairs up unrelated
+    /// elements.
+    pub fn pair_list_elements(mut self, pair_list_elements: bool) -> Self {
+        self.pair_list_elements = pair_list_elements;
+        self
+    }
+
+    /// Decides per field name whether the list or tuple directly in that field is sorted,
+    /// eg. `&[("events", FieldKind::Ordered), ("tags", FieldKind::Unordered)]`. Fields that
+    /// aren't listed follow [`sort_sequences`](Self::sort_sequences), as do collections
+    /// nested deeper inside a listed field.
+    pub fn schema(mut self, schema: &[(&str, FieldKind)]) -> Self {
+        self.schema = schema
+            .iter()
+            .map(|(field, kind)| (field.to_string(), *kind))
+            .collect();
+        self
+    }
+
+    /// When a comparison fails, diff each differing top-level map entry or struct field
+    /// separately, under a header with its key, instead of diffing the whole Debug output.
+    /// This reads like a per-section report for large configurations. Values that aren't
+    /// maps or structs are diffed as usual.
+    pub fn group_by_top_key(mut self, group_by_top_key: bool) -> Self {
+        self.group_by_top_key = group_by_top_key;
+        self
+    }
+
+    /// Only sort the maps and sets directly in the struct fields with the given names, eg.
+    /// `&["headers"]`, and compare all other maps and sets in their original order. This
+    /// inverts the default for projects where most maps are ordered (eg. `BTreeMap` or
+    /// `IndexMap`). Lists and tuples still follow [`sort_sequences`](Self::sort_sequences).
+    pub fn unordered_only(mut self, fields: &[&str]) -> Self {
+        self.unordered_only = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+
+    /// Compare floats as equal when they differ by at most `tolerance`, eg. `1e-9`, so
+    /// golden tests don't fail on floats that are rendered slightly differently across
+    /// platforms. The result doesn't depend on which side a float is on: the diff shows
+    /// the shorter of the two floats for both.
+    pub fn float_tolerance(mut self, tolerance: f64) -> Self {
+        self.float_tolerance = Some(tolerance);
+        self
+    }
+
+    /// Replace single-field structs and tuple structs with the given names, eg.
+    /// `&["Wrapper"]`, by their only field, so that `Wrapper { inner: Foo }` compares equal
+    /// to `Foo`. Useful for comparing values from before and after a refactor that added a
+    /// wrapper type.
+    pub fn transparent_wrappers(mut self, names: &[&str]) -> Self {
+        self.transparent_wrappers = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// When a comparison fails, start the failure message with a one-line overview of the
+    /// differences, eg. `3 keys added, 1 removed, 2 changed`. Map entries, struct fields and
+    /// list elements each count as a key.
+    pub fn summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// Compare the floats `-0.0` and `0.0` as equal, like `==` does. By default they're
+    /// different, since their Debug output differs.
+    pub fn treat_signed_zero_equal(mut self, treat_signed_zero_equal: bool) -> Self {
+        self.treat_signed_zero_equal = treat_signed_zero_equal;
+        self
+    }
+
+    /// Treat lists of 2-tuples, eg. a `Vec<(String, i32)>`, as maps from the first to the
+    /// second element of each tuple, so they're sorted and compared by key regardless of
+    /// [`sort_sequences`](Self::sort_sequences). Empty lists stay lists.
+    pub fn treat_pair_lists_as_maps(mut self, treat_pair_lists_as_maps: bool) -> Self {
+        self.treat_pair_lists_as_maps = treat_pair_lists_as_maps;
+        self
+    }
+
+    /// Compare `Cell` and `RefCell` values by their contents (default: `true`). Their Debug
+    /// output, eg. `RefCell { value: {..} }`, is replaced by the `value` field, so
+    /// interior-mutable structures diff on their content. Only structs whose single field is
+    /// `value` are unwrapped, a user-defined `Cell { row, col, value }` is compared as-is. A
+    /// `RefCell` that's mutably borrowed while formatting shows as `<borrowed>`.
+    pub fn unwrap_cells(mut self, unwrap_cells: bool) -> Self {
+        self.unwrap_cells = unwrap_cells;
+        self
+    }
+
+    /// Rewrites map keys (strings and identifiers) and struct field names with
+    /// `key_transform` before sorting and comparing, eg. to strip a prefix:
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::SortConfig;
+    ///
+    /// let config = SortConfig::new().key_transform(Box::new(|key| {
+    ///     key.strip_prefix("col_").unwrap_or(key).to_string()
+    /// }));
+    /// ```
+    pub fn key_transform(mut self, key_transform: KeyTransformFn) -> Self {
+        self.key_transform = Some(key_transform);
+        self
+    }
+
+    /// When a comparison fails, show both sides next to each other in two aligned columns
+    /// instead of an interleaved diff, like `diff --side-by-side`. Changed lines are marked
+    /// with `|`, lines only on the left with `<` and lines only on the right with `>`.
+    pub fn side_by_side(mut self, side_by_side: bool) -> Self {
+        self.side_by_side = side_by_side;
+        self
+    }
+
+    /// Checks an invariant of each side's sorted tree before comparing, eg. that no map has
+    /// duplicate keys. If `validate` returns an error for either side, the assertion fails
+    /// with its message even if both sides are equal.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::{SortConfig, Value};
+    ///
+    /// let config = SortConfig::new().validate(Box::new(|value| match value {
+    ///     Value::List(list) if list.values.is_empty() => Err("empty list".to_string()),
+    ///     _ => Ok(()),
+    /// }));
+    /// ```
+    pub fn validate(mut self, validate: ValidateFn) -> Self {
+        self.validate = Some(validate);
+        self
+    }
+
+    /// Never sort the collection directly in the struct fields or string map keys with the
+    /// given names, eg. `&["stages"]` for an insertion-ordered map, keeping its original
+    /// order. Maps and lists nested inside it are still sorted as usual.
+    pub fn blocklist(mut self, fields: &[&str]) -> Self {
+        self.blocklist = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+
+    /// Pass as long as the sorted values differ in at most `max_differences` places (eg.
+    /// changed, added or removed map entries), for "close enough" comparisons. When there
+    /// are more, all differences are listed after the diff.
+    pub fn max_differences(mut self, max_differences: usize) -> Self {
+        self.max_differences = Some(max_differences);
+        self
+    }
+
+    /// How many levels deep values are parsed and sorted (default: 128). Values nested
+    /// deeper are kept verbatim and left unsorted, so that deeply nested values (eg. a
+    /// large syntax tree) can't overflow the stack. Raise it to sort deeper values, at the
+    /// cost of more stack space.
+    pub fn max_sort_depth(mut self, max_sort_depth: usize) -> Self {
+        self.max_sort_depth = max_sort_depth;
+        self
+    }
+
+    /// Print a warning to stderr when a value is nested deeper than
+    /// [`max_sort_depth`](Self::max_sort_depth) and is left unsorted. The warning is
+    /// printed at most once per process, however many values are affected.
+    pub fn warn_max_sort_depth(mut self, warn_max_sort_depth: bool) -> Self {
+        self.warn_max_sort_depth = warn_max_sort_depth;
+        self
+    }
+
+    /// Orders map keys and the elements of sets (and sorted lists and tuples) with
+    /// `comparator` instead of their default order, eg. to order `Priority(3)` before
+    /// `Priority(10)` by the number inside. Values that `comparator` considers equal are
+    /// ordered as usual, so the output stays deterministic.
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::{SortConfig, Term, Value};
+    ///
+    /// // Orders numbers numerically.
+    /// let config = SortConfig::new().comparator(Box::new(|a, b| match (a, b) {
+    ///     (Value::Term(Term::UnquotedRawString(a)), Value::Term(Term::UnquotedRawString(b))) => {
+    ///         match (a.parse::<f64>(), b.parse::<f64>()) {
+    ///             (Ok(a), Ok(b)) => a.total_cmp(&b),
+    ///             _ => std::cmp::Ordering::Equal,
+    ///         }
+    ///     }
+    ///     _ => std::cmp::Ordering::Equal,
+    /// }));
+    /// ```
+    pub fn comparator(mut self, comparator: ComparatorFn) -> Self {
+        self.comparator = Some(comparator);
+        self
+    }
+
+    /// Order map entries by their value first and only then by their key, eg. to group
+    /// the keys that map to the same value. Entries are always ordered by both, so maps
+    /// with duplicate keys (eg. from a multimap's Debug output) sort deterministically
+    /// either way.
+    pub fn sort_by_value(mut self, sort_by_value: bool) -> Self {
+        self.sort_by_value = sort_by_value;
+        self
+    }
+}
@@ -0,0 +1,996 @@
+//! Structural comparison of two sorted [`Value`] trees, used to annotate the line diff with
+//! extra information.
+use std::fmt;
+use std::ops::ControlFlow;
+
+use darrentsung_debug_parser::{IdentValue, KeyValue, OrNonExhaustive, Struct, Value};
+
+use crate::normalize::clone_value;
+use crate::sort::sort_maps;
+use crate::SortConfig;
+
+/// An element that appears in both lists, but at a different position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Move {
+    /// Path to the list containing the element, eg. `.foo["key"]`.
+    pub path: String,
+    pub element: String,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{path}[{}] -> {path}[{}]: {}",
+            self.from,
+            self.to,
+            self.element,
+            path = self.path
+        )
+    }
+}
+
+/// Finds the elements of lists and tuples that were moved between `left` and `right`.
+///
+/// Elements that are part of the longest common subsequence of the two lists are
+/// considered to be in place, every other element that appears on both sides is reported
+/// as moved.
+pub(crate) fn list_moves(left: &Value, right: &Value) -> Vec<Move> {
+    let mut moves = Vec::new();
+    collect_moves(left, right, String::new(), &mut moves);
+    moves
+}
+
+fn collect_moves(left: &Value, right: &Value, path: String, moves: &mut Vec<Move>) {
+    match (left, right) {
+        (Value::Struct(l), Value::Struct(r)) if l.name == r.name => {
+            for l_field in &l.values {
+                let OrNonExhaustive::Value(l_field) = l_field else {
+                    continue;
+                };
+                let r_field = r.values.iter().find_map(|r_field| match r_field {
+                    OrNonExhaustive::Value(r_field) if r_field.ident == l_field.ident => {
+                        Some(r_field)
+                    }
+                    _ => None,
+                });
+                if let Some(r_field) = r_field {
+                    let path = format!("{}.{}", path, l_field.ident);
+                    collect_moves(&l_field.value, &r_field.value, path, moves);
+                }
+            }
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            for l_entry in &l.values {
+                if let Some(r_entry) = r.values.iter().find(|r_entry| r_entry.key == l_entry.key) {
+                    let path = format!("{}[{:?}]", path, l_entry.key);
+                    collect_moves(&l_entry.value, &r_entry.value, path, moves);
+                }
+            }
+        }
+        (Value::List(l), Value::List(r)) => {
+            collect_sequence_moves(&l.values, &r.values, path, moves);
+        }
+        (Value::Tuple(l), Value::Tuple(r)) if l.name == r.name => {
+            collect_sequence_moves(&l.values, &r.values, path, moves);
+        }
+        _ => (),
+    }
+}
+
+fn collect_sequence_moves(left: &[Value], right: &[Value], path: String, moves: &mut Vec<Move>) {
+    let left_rendered: Vec<String> = left.iter().map(|v| format!("{:?}", v)).collect();
+    let right_rendered: Vec<String> = right.iter().map(|v| format!("{:?}", v)).collect();
+
+    let (mut left_in_place, mut right_in_place) = lcs_indices(&left_rendered, &right_rendered);
+    let sequence_moves_len = moves.len();
+    for from in 0..left.len() {
+        if left_in_place[from] {
+            continue;
+        }
+        let to = (0..right.len())
+            .find(|&to| !right_in_place[to] && right_rendered[to] == left_rendered[from]);
+        if let Some(to) = to {
+            left_in_place[from] = true;
+            right_in_place[to] = true;
+            moves.push(Move {
+                path: path.clone(),
+                element: left_rendered[from].clone(),
+                from,
+                to,
+            });
+        }
+    }
+
+    // Only compare elements positionally when the list wasn't reordered, otherwise the
+    // pairs don't correspond to each other.
+    if moves.len() == sequence_moves_len && left.len() == right.len() {
+        for (idx, (l, r)) in left.iter().zip(right).enumerate() {
+            collect_moves(l, r, format!("{}[{}]", path, idx), moves);
+        }
+    }
+}
+
+/// A list element that only appears on one side of the comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnmatchedElement {
+    /// Either `"left"` or `"right"`.
+    pub side: &'static str,
+    /// Path to the list containing the element, eg. `.foo["key"]`.
+    pub path: String,
+    pub element: String,
+    /// Index of the element in the original (unsorted) list.
+    pub original_index: usize,
+}
+
+impl fmt::Display for UnmatchedElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}[{}]: {}",
+            self.side, self.path, self.original_index, self.element
+        )
+    }
+}
+
+/// Finds the list elements that only appear on one side, given the `(sorted, unsorted)`
+/// trees of both sides. The unsorted trees are used to look up where each element was
+/// before sorting.
+pub(crate) fn unmatched_elements(
+    left: (&Value, &Value),
+    right: (&Value, &Value),
+    config: &SortConfig,
+) -> Vec<UnmatchedElement> {
+    let mut unmatched = Vec::new();
+    collect_unmatched(left, right, String::new(), config, &mut unmatched);
+    unmatched
+}
+
+fn collect_unmatched(
+    (left, left_unsorted): (&Value, &Value),
+    (right, right_unsorted): (&Value, &Value),
+    path: String,
+    config: &SortConfig,
+    unmatched: &mut Vec<UnmatchedElement>,
+) {
+    match (left, right, left_unsorted, right_unsorted) {
+        (Value::Struct(l), Value::Struct(r), Value::Struct(lu), Value::Struct(ru))
+            if l.name == r.name =>
+        {
+            for l_field in fields(l) {
+                let ident = &l_field.ident;
+                let (Some(r_field), Some(lu_field), Some(ru_field)) = (
+                    fields(r).find(|f| f.ident == *ident),
+                    fields(lu).find(|f| f.ident == *ident),
+                    fields(ru).find(|f| f.ident == *ident),
+                ) else {
+                    continue;
+                };
+                collect_unmatched(
+                    (&l_field.value, &lu_field.value),
+                    (&r_field.value, &ru_field.value),
+                    format!("{}.{}", path, ident),
+                    config,
+                    unmatched,
+                );
+            }
+        }
+        (Value::Map(l), Value::Map(r), Value::Map(lu), Value::Map(ru)) => {
+            for l_entry in &l.values {
+                let key = format!("{:?}", l_entry.key);
+                let (Some(r_entry), Some(lu_entry), Some(ru_entry)) = (
+                    r.values.iter().find(|r_entry| r_entry.key == l_entry.key),
+                    find_by_canonical_key(&lu.values, &key, config),
+                    find_by_canonical_key(&ru.values, &key, config),
+                ) else {
+                    continue;
+                };
+                collect_unmatched(
+                    (&l_entry.value, &lu_entry.value),
+                    (&r_entry.value, &ru_entry.value),
+                    format!("{}[{}]", path, key),
+                    config,
+                    unmatched,
+                );
+            }
+        }
+        (Value::List(l), Value::List(r), Value::List(lu), Value::List(ru)) => {
+            let left_rendered: Vec<String> = l.values.iter().map(|v| format!("{:?}", v)).collect();
+            let right_rendered: Vec<String> = r.values.iter().map(|v| format!("{:?}", v)).collect();
+
+            let mut right_matched = vec![false; right_rendered.len()];
+            let mut left_only = Vec::new();
+            for element in left_rendered {
+                match (0..right_rendered.len())
+                    .find(|&idx| !right_matched[idx] && right_rendered[idx] == element)
+                {
+                    Some(idx) => right_matched[idx] = true,
+                    None => left_only.push(element),
+                }
+            }
+            let right_only = right_rendered
+                .into_iter()
+                .zip(right_matched)
+                .filter(|(_, matched)| !matched)
+                .map(|(element, _)| element);
+
+            for (side, elements, unsorted) in [
+                ("left", left_only, &lu.values),
+                ("right", right_only.collect(), &ru.values),
+            ] {
+                let mut unsorted_rendered: Vec<Option<String>> = unsorted
+                    .iter()
+                    .map(|v| Some(canonical(v, config)))
+                    .collect();
+                for element in elements {
+                    let original_index = unsorted_rendered
+                        .iter()
+                        .position(|rendered| rendered.as_deref() == Some(element.as_str()));
+                    if let Some(original_index) = original_index {
+                        unsorted_rendered[original_index] = None;
+                        unmatched.push(UnmatchedElement {
+                            side,
+                            path: path.clone(),
+                            element,
+                            original_index,
+                        });
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+fn fields(s: &Struct) -> impl Iterator<Item = &IdentValue> {
+    s.values.iter().filter_map(
+        |ident_value_or_non_exhaustive| match ident_value_or_non_exhaustive {
+            OrNonExhaustive::Value(ident_value) => Some(ident_value),
+            OrNonExhaustive::NonExhaustive => None,
+        },
+    )
+}
+
+/// Finds the entry of an unsorted map whose key renders as `key` once sorted.
+fn find_by_canonical_key<'a>(
+    entries: &'a [KeyValue],
+    key: &str,
+    config: &SortConfig,
+) -> Option<&'a KeyValue> {
+    entries
+        .iter()
+        .find(|entry| canonical(&entry.key, config) == key)
+}
+
+/// Renders the sorted form of an unsorted value.
+fn canonical(v: &Value, config: &SortConfig) -> String {
+    let mut v = clone_value(v);
+    sort_maps(&mut v, config);
+    format!("{:?}", v)
+}
+
+/// The first path at which two sorted trees differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Difference {
+    /// Path to the differing value, eg. `.foo["key"][2]`, empty for the root.
+    pub path: String,
+    /// The value on the left side, `None` if it's missing.
+    pub left: Option<String>,
+    /// The value on the right side, `None` if it's missing.
+    pub right: Option<String>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "First difference at {}:", self.display_path())?;
+        for (side, value) in [("left", &self.left), ("right", &self.right)] {
+            match value {
+                Some(value) => write!(f, "\n  {}: {}", side, value)?,
+                None => write!(f, "\n  {}: <missing>", side)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Difference {
+    /// Formats the difference on a single line, eg. `[1].name: "a" != "b"`.
+    pub fn summary(&self) -> String {
+        let side = |value: &Option<String>| value.as_deref().unwrap_or("<missing>").to_string();
+        format!(
+            "{}: {} != {}",
+            self.display_path(),
+            side(&self.left),
+            side(&self.right)
+        )
+    }
+
+    fn display_path(&self) -> &str {
+        if self.path.is_empty() {
+            "<root>"
+        } else {
+            &self.path
+        }
+    }
+}
+
+/// Counts of the keys that differ between two values, see [`SortConfig::summary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Summary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+impl Summary {
+    pub(crate) fn new(differences: &[Difference]) -> Self {
+        let mut summary = Self::default();
+        for difference in differences {
+            match (&difference.left, &difference.right) {
+                (None, _) => summary.added += 1,
+                (_, None) => summary.removed += 1,
+                _ => summary.changed += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} added, {} removed, {} changed",
+            self.added,
+            if self.added == 1 { "key" } else { "keys" },
+            self.removed,
+            self.changed
+        )
+    }
+}
+
+/// Formats `differences` as a JSON document of the added, removed and changed paths, eg.
+/// `{"added":[{"path":".b","after":"2"}],"removed":[],"changed":[]}`. Values are their
+/// Debug output as JSON strings.
+pub(crate) fn json_report(differences: &[Difference]) -> String {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for difference in differences {
+        let path = json_string(&difference.path);
+        match (&difference.left, &difference.right) {
+            (None, Some(after)) => {
+                added.push(format!(
+                    r#"{{"path":{},"after":{}}}"#,
+                    path,
+                    json_string(after)
+                ));
+            }
+            (Some(before), None) => {
+                removed.push(format!(
+                    r#"{{"path":{},"before":{}}}"#,
+                    path,
+                    json_string(before)
+                ));
+            }
+            (Some(before), Some(after)) => changed.push(format!(
+                r#"{{"path":{},"before":{},"after":{}}}"#,
+                path,
+                json_string(before),
+                json_string(after)
+            )),
+            (None, None) => (),
+        }
+    }
+    format!(
+        r#"{{"added":[{}],"removed":[{}],"changed":[{}]}}"#,
+        added.join(","),
+        removed.join(","),
+        changed.join(",")
+    )
+}
+
+/// Formats `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Finds the first path at which `left` and `right` differ, without looking at the rest of
+/// the trees.
+pub(crate) fn first_difference(left: &Value, right: &Value) -> Option<Difference> {
+    let mut first = None;
+    let _ = find_differences(left, right, String::new(), &mut |difference| {
+        first = Some(difference);
+        ControlFlow::Break(())
+    });
+    first
+}
+
+/// Lists every path at which `left` and `right` differ. List and tuple elements are paired
+/// up by position, so an element that differs in a single field is reported as that field
+/// instead of as a replaced element.
+pub(crate) fn differences(left: &Value, right: &Value) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let _ = find_differences(left, right, String::new(), &mut |difference| {
+        differences.push(difference);
+        ControlFlow::Continue(())
+    });
+    differences
+}
+
+/// Passes the differences between `left` and `right` to `found` until it breaks.
+fn find_differences(
+    left: &Value,
+    right: &Value,
+    path: String,
+    found: &mut impl FnMut(Difference) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    match (left, right) {
+        (Value::Struct(l), Value::Struct(r)) if l.name == r.name => {
+            for l_field in fields(l) {
+                let path = format!("{}.{}", path, l_field.ident);
+                match fields(r).find(|r_field| r_field.ident == l_field.ident) {
+                    Some(r_field) => find_differences(&l_field.value, &r_field.value, path, found)?,
+                    None => found(one_sided(path, Some(&l_field.value), None))?,
+                }
+            }
+            for r_field in
+                fields(r).filter(|r_field| !fields(l).any(|l_field| l_field.ident == r_field.ident))
+            {
+                let path = format!("{}.{}", path, r_field.ident);
+                found(one_sided(path, None, Some(&r_field.value)))?;
+            }
+            if is_non_exhaustive(l) != is_non_exhaustive(r) {
+                found(one_sided(path, Some(left), Some(right)))?;
+            }
+            ControlFlow::Continue(())
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            for l_entry in &l.values {
+                let path = format!("{}[{:?}]", path, l_entry.key);
+                match r.values.iter().find(|r_entry| r_entry.key == l_entry.key) {
+                    Some(r_entry) => find_differences(&l_entry.value, &r_entry.value, path, found)?,
+                    None => found(one_sided(path, Some(&l_entry.value), None))?,
+                }
+            }
+            for r_entry in r
+                .values
+                .iter()
+                .filter(|r_entry| !l.values.iter().any(|l_entry| l_entry.key == r_entry.key))
+            {
+                let path = format!("{}[{:?}]", path, r_entry.key);
+                found(one_sided(path, None, Some(&r_entry.value)))?;
+            }
+            ControlFlow::Continue(())
+        }
+        (Value::Set(l), Value::Set(r)) => set_differences(&l.values, &r.values, path, found),
+        (Value::List(l), Value::List(r)) => sequence_differences(&l.values, &r.values, path, found),
+        (Value::Tuple(l), Value::Tuple(r)) if l.name == r.name => {
+            sequence_differences(&l.values, &r.values, path, found)
+        }
+        _ if left != right => found(one_sided(path, Some(left), Some(right))),
+        _ => ControlFlow::Continue(()),
+    }
+}
+
+fn is_non_exhaustive(s: &Struct) -> bool {
+    s.values
+        .iter()
+        .any(|v| matches!(v, OrNonExhaustive::NonExhaustive))
+}
+
+fn sequence_differences(
+    left: &[Value],
+    right: &[Value],
+    path: String,
+    found: &mut impl FnMut(Difference) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    for idx in 0..left.len().max(right.len()) {
+        let element_path = format!("{}[{}]", path, idx);
+        match (left.get(idx), right.get(idx)) {
+            (Some(l), Some(r)) => find_differences(l, r, element_path, found)?,
+            (l, r) => found(one_sided(element_path, l, r))?,
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// Matches the elements of two sets by equality, so that inserting an element only reports
+/// that element instead of shifting every element after it. Unmatched elements are
+/// reported as removed or added at their index.
+fn set_differences(
+    left: &[Value],
+    right: &[Value],
+    path: String,
+    found: &mut impl FnMut(Difference) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut right_matched = vec![false; right.len()];
+    let mut left_unmatched = vec![];
+    for (idx, l) in left.iter().enumerate() {
+        match (0..right.len()).find(|&r_idx| !right_matched[r_idx] && right[r_idx] == *l) {
+            Some(r_idx) => right_matched[r_idx] = true,
+            None => left_unmatched.push(idx),
+        }
+    }
+    for idx in left_unmatched {
+        found(one_sided(
+            format!("{}[{}]", path, idx),
+            Some(&left[idx]),
+            None,
+        ))?;
+    }
+    for (idx, r) in right.iter().enumerate() {
+        if !right_matched[idx] {
+            found(one_sided(format!("{}[{}]", path, idx), None, Some(r)))?;
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+fn one_sided(path: String, left: Option<&Value>, right: Option<&Value>) -> Difference {
+    Difference {
+        path,
+        left: left.map(|v| format!("{:?}", v)),
+        right: right.map(|v| format!("{:?}", v)),
+    }
+}
+
+/// Two structs or tuple structs at the same path that have different type names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TypeMismatch {
+    /// Path to the values, eg. `.foo["key"]`, empty for the root.
+    pub path: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "type mismatch: {} vs {}", self.left, self.right)
+        } else {
+            write!(
+                f,
+                "type mismatch at {}: {} vs {}",
+                self.path, self.left, self.right
+            )
+        }
+    }
+}
+
+/// Finds the structs and tuple structs whose type names differ between `left` and
+/// `right`. Their fields are still compared by name, so mismatches nested inside them are
+/// found as well.
+pub(crate) fn type_mismatches(left: &Value, right: &Value) -> Vec<TypeMismatch> {
+    let mut mismatches = Vec::new();
+    collect_type_mismatches(left, right, String::new(), &mut mismatches);
+    mismatches
+}
+
+fn collect_type_mismatches(
+    left: &Value,
+    right: &Value,
+    path: String,
+    mismatches: &mut Vec<TypeMismatch>,
+) {
+    let mismatch = |left: &str, right: &str| TypeMismatch {
+        path: path.clone(),
+        left: left.to_string(),
+        right: right.to_string(),
+    };
+    match (left, right) {
+        (Value::Struct(l), Value::Struct(r)) => {
+            if l.name != r.name {
+                mismatches.push(mismatch(&l.name, &r.name));
+            }
+            for l_field in fields(l) {
+                if let Some(r_field) = fields(r).find(|r_field| r_field.ident == l_field.ident) {
+                    let path = format!("{}.{}", path, l_field.ident);
+                    collect_type_mismatches(&l_field.value, &r_field.value, path, mismatches);
+                }
+            }
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            for l_entry in &l.values {
+                if let Some(r_entry) = r.values.iter().find(|r_entry| r_entry.key == l_entry.key) {
+                    let path = format!("{}[{:?}]", path, l_entry.key);
+                    collect_type_mismatches(&l_entry.value, &r_entry.value, path, mismatches);
+                }
+            }
+        }
+        (Value::List(l), Value::List(r)) => {
+            collect_sequence_type_mismatches(&l.values, &r.values, path, mismatches);
+        }
+        (Value::Tuple(l), Value::Tuple(r)) => {
+            if let (Some(l_name), Some(r_name)) = (&l.name, &r.name) {
+                if l_name != r_name {
+                    mismatches.push(mismatch(l_name, r_name));
+                }
+            }
+            collect_sequence_type_mismatches(&l.values, &r.values, path, mismatches);
+        }
+        _ => (),
+    }
+}
+
+fn collect_sequence_type_mismatches(
+    left: &[Value],
+    right: &[Value],
+    path: String,
+    mismatches: &mut Vec<TypeMismatch>,
+) {
+    for (idx, (l, r)) in left.iter().zip(right).enumerate() {
+        collect_type_mismatches(l, r, format!("{}[{}]", path, idx), mismatches);
+    }
+}
+
+/// A top-level map entry or struct field, with its value on both sides.
+pub(crate) struct TopLevelEntry<'a> {
+    /// The field name or the Debug output of the map key.
+    pub key: String,
+    /// The value on the left side, `None` if it's missing.
+    pub left: Option<&'a Value>,
+    /// The value on the right side, `None` if it's missing.
+    pub right: Option<&'a Value>,
+}
+
+/// Pairs up the top-level map entries or struct fields of `left` and `right`, in the order
+/// of the left side followed by the ones only on the right side. Returns `None` unless
+/// both sides are maps or structs of the same type.
+pub(crate) fn top_level_entries<'a>(
+    left: &'a Value,
+    right: &'a Value,
+) -> Option<Vec<TopLevelEntry<'a>>> {
+    type Entries<'a> = Vec<(String, &'a Value)>;
+    let (left_entries, right_entries): (Entries, Entries) = match (left, right) {
+        (Value::Struct(l), Value::Struct(r)) if l.name == r.name => {
+            let entries = |s: &'a Struct| {
+                fields(s)
+                    .map(|field| (field.ident.clone(), &field.value))
+                    .collect()
+            };
+            (entries(l), entries(r))
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            let entries = |m: &'a [KeyValue]| {
+                m.iter()
+                    .map(|entry| (format!("{:?}", entry.key), &entry.value))
+                    .collect()
+            };
+            (entries(&l.values), entries(&r.values))
+        }
+        _ => return None,
+    };
+
+    let find = |entries: &[(String, &'a Value)], key: &str| {
+        entries
+            .iter()
+            .find(|(other_key, _)| other_key == key)
+            .map(|(_, value)| *value)
+    };
+    let mut entries: Vec<TopLevelEntry> = left_entries
+        .iter()
+        .map(|(key, value)| TopLevelEntry {
+            key: key.clone(),
+            left: Some(value),
+            right: find(&right_entries, key),
+        })
+        .collect();
+    entries.extend(
+        right_entries
+            .iter()
+            .filter(|(key, _)| find(&left_entries, key).is_none())
+            .map(|(key, value)| TopLevelEntry {
+                key: key.clone(),
+                left: None,
+                right: Some(value),
+            }),
+    );
+    Some(entries)
+}
+
+/// Returns the elements of the longest common subsequence of `left` and `right`.
+pub(crate) fn longest_common_subsequence(left: Vec<Value>, right: &[Value]) -> Vec<Value> {
+    let (left_in_lcs, _) = lcs_indices(&left, right);
+    left.into_iter()
+        .zip(left_in_lcs)
+        .filter(|(_, in_lcs)| *in_lcs)
+        .map(|(v, _)| v)
+        .collect()
+}
+
+/// Lines of unchanged context shown around each change of a unified diff.
+const UNIFIED_DIFF_CONTEXT: usize = 3;
+
+/// Formats the line diff of `left` and `right` as a unified diff, like `diff -u`.
+pub(crate) fn unified_diff(left: &str, right: &str) -> String {
+    let left_lines: Vec<&str> = left.lines().collect();
+    let right_lines: Vec<&str> = right.lines().collect();
+    let (left_in_lcs, right_in_lcs) = lcs_indices(&left_lines, &right_lines);
+
+    // Each line of the diff, along with the line indices it starts at on both sides.
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left_lines.len() || j < right_lines.len() {
+        if i < left_lines.len() && !left_in_lcs[i] {
+            ops.push(('-', left_lines[i], i, j));
+            i += 1;
+        } else if j < right_lines.len() && !right_in_lcs[j] {
+            ops.push(('+', right_lines[j], i, j));
+            j += 1;
+        } else {
+            ops.push((' ', left_lines[i], i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+
+    let mut output = String::from("--- left\n+++ right");
+    let changes: Vec<usize> = (0..ops.len()).filter(|&idx| ops[idx].0 != ' ').collect();
+    let mut changes = changes.into_iter().peekable();
+    while let Some(first_change) = changes.next() {
+        // Extend the hunk while the next change is close enough to share context.
+        let mut last_change = first_change;
+        while let Some(&next_change) = changes.peek() {
+            if next_change - last_change > 2 * UNIFIED_DIFF_CONTEXT {
+                break;
+            }
+            last_change = next_change;
+            changes.next();
+        }
+
+        let start = first_change.saturating_sub(UNIFIED_DIFF_CONTEXT);
+        let end = (last_change + UNIFIED_DIFF_CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+        let left_len = hunk.iter().filter(|op| op.0 != '+').count();
+        let right_len = hunk.iter().filter(|op| op.0 != '-').count();
+        let (_, _, left_start, right_start) = hunk[0];
+        // Empty ranges refer to the line before them, as in `diff -u`.
+        let range_start = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        output.push_str(&format!(
+            "\n@@ -{},{} +{},{} @@",
+            range_start(left_start, left_len),
+            left_len,
+            range_start(right_start, right_len),
+            right_len
+        ));
+        for (prefix, line, _, _) in hunk {
+            output.push_str(&format!("\n{}{}", prefix, line));
+        }
+    }
+    output
+}
+
+/// Formats the line diff of `left` and `right` as two aligned columns, like
+/// `diff --side-by-side`.
+pub(crate) fn side_by_side(left: &str, right: &str) -> String {
+    let left_lines: Vec<&str> = left.lines().collect();
+    let right_lines: Vec<&str> = right.lines().collect();
+    let (left_in_lcs, right_in_lcs) = lcs_indices(&left_lines, &right_lines);
+    let width = left_lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut rows = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left_lines.len() || j < right_lines.len() {
+        // Lines between two common lines are paired up as changes, the rest are added or
+        // removed.
+        let removed_end = (i..left_lines.len())
+            .find(|&idx| left_in_lcs[idx])
+            .unwrap_or(left_lines.len());
+        let added_end = (j..right_lines.len())
+            .find(|&idx| right_in_lcs[idx])
+            .unwrap_or(right_lines.len());
+        while i < removed_end || j < added_end {
+            rows.push(match (i < removed_end, j < added_end) {
+                (true, true) => format!("{:<width$} | {}", left_lines[i], right_lines[j]),
+                (true, false) => format!("{:<width$} <", left_lines[i]),
+                _ => format!("{:<width$} > {}", "", right_lines[j]),
+            });
+            i += usize::from(i < removed_end);
+            j += usize::from(j < added_end);
+        }
+
+        if i < left_lines.len() && j < right_lines.len() {
+            rows.push(format!("{:<width$}   {}", left_lines[i], right_lines[j]));
+            i += 1;
+            j += 1;
+        }
+    }
+    rows.join("\n")
+}
+
+/// Returns which indices of `left` and `right` are part of their longest common
+/// subsequence.
+fn lcs_indices<T: PartialEq>(left: &[T], right: &[T]) -> (Vec<bool>, Vec<bool>) {
+    let mut left_in_lcs = vec![false; left.len()];
+    let mut right_in_lcs = vec![false; right.len()];
+    mark_lcs(left, right, &mut left_in_lcs, &mut right_in_lcs);
+    (left_in_lcs, right_in_lcs)
+}
+
+/// Marks the elements of a longest common subsequence of `left` and `right` with
+/// Hirschberg's algorithm, which only keeps a single row of the LCS table at a time so that
+/// large, mostly different inputs don't need quadratic memory.
+fn mark_lcs<T: PartialEq>(
+    left: &[T],
+    right: &[T],
+    left_in_lcs: &mut [bool],
+    right_in_lcs: &mut [bool],
+) {
+    // A shared prefix and suffix are always part of the LCS.
+    let prefix = left.iter().zip(right).take_while(|(l, r)| l == r).count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    for in_lcs in [&mut *left_in_lcs, &mut *right_in_lcs] {
+        let len = in_lcs.len();
+        in_lcs[..prefix].fill(true);
+        in_lcs[len - suffix..].fill(true);
+    }
+    let (left, right) = (
+        &left[prefix..left.len() - suffix],
+        &right[prefix..right.len() - suffix],
+    );
+    let left_in_lcs = &mut left_in_lcs[prefix..prefix + left.len()];
+    let right_in_lcs = &mut right_in_lcs[prefix..prefix + right.len()];
+
+    match left {
+        [] => (),
+        [element] => {
+            if let Some(idx) = right.iter().position(|r| r == element) {
+                left_in_lcs[0] = true;
+                right_in_lcs[idx] = true;
+            }
+        }
+        _ => {
+            // Split `right` where the LCS of the first half of `left` with a prefix of
+            // `right` and of the second half with the rest of `right` is the longest.
+            let mid = left.len() / 2;
+            let forward = lcs_lengths(&left[..mid], right, false);
+            let backward = lcs_lengths(&left[mid..], right, true);
+            let split = (0..=right.len())
+                .rev()
+                .max_by_key(|&j| forward[j] + backward[right.len() - j])
+                .expect("there's always a split");
+
+            let (left_first, left_second) = left_in_lcs.split_at_mut(mid);
+            let (right_first, right_second) = right_in_lcs.split_at_mut(split);
+            mark_lcs(&left[..mid], &right[..split], left_first, right_first);
+            mark_lcs(&left[mid..], &right[split..], left_second, right_second);
+        }
+    }
+}
+
+/// Returns the length of the LCS of `left` and each prefix of `right`, or of each suffix
+/// (by length) if `reverse` is set.
+fn lcs_lengths<T: PartialEq>(left: &[T], right: &[T], reverse: bool) -> Vec<usize> {
+    let at = |slice: &'_ [T], idx: usize| {
+        if reverse {
+            slice.len() - 1 - idx
+        } else {
+            idx
+        }
+    };
+    let mut row = vec![0; right.len() + 1];
+    for i in 0..left.len() {
+        let mut diagonal = 0;
+        for j in 0..right.len() {
+            let above = row[j + 1];
+            row[j + 1] = if left[at(left, i)] == right[at(right, j)] {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn finds_single_moved_element() {
+        let left = parse(r#"Foo { log: ["a", "b", "c"] }"#).unwrap();
+        let right = parse(r#"Foo { log: ["b", "c", "a"] }"#).unwrap();
+
+        let moves = list_moves(&left, &right);
+        assert_eq!(
+            moves.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![r#".log[0] -> .log[2]: "a""#]
+        );
+    }
+
+    #[test]
+    fn formats_side_by_side_diff() {
+        let left = "{\n    a: 1,\n    b: 2,\n    c: 3,\n}";
+        let right = "{\n    a: 1,\n    b: 20,\n    d: 4,\n    e: 5,\n}";
+        assert_eq!(
+            side_by_side(left, right),
+            "{           {\n\
+             \x20   a: 1,       a: 1,\n\
+             \x20   b: 2, |     b: 20,\n\
+             \x20   c: 3, |     d: 4,\n\
+             \x20         >     e: 5,\n\
+             }           }"
+        );
+    }
+
+    #[test]
+    fn formats_unified_diff() {
+        let left = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj";
+        let right = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk";
+        assert_eq!(
+            unified_diff(left, right),
+            "--- left\n+++ right\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k"
+        );
+    }
+
+    #[test]
+    fn trims_shared_prefix_and_suffix_of_long_sequences() {
+        // Without trimming, finding the LCS of these would take quadratic time.
+        let left: Vec<u32> = (0..100_000).collect();
+        let mut right = left.clone();
+        right[50_000] = 0;
+        right.insert(50_002, 1);
+
+        let (left_in_lcs, right_in_lcs) = lcs_indices(&left, &right);
+        assert_eq!(left_in_lcs.iter().filter(|in_lcs| !**in_lcs).count(), 1);
+        assert!(!left_in_lcs[50_000]);
+        assert_eq!(
+            right_in_lcs
+                .iter()
+                .enumerate()
+                .filter(|(_, in_lcs)| !**in_lcs)
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>(),
+            vec![50_000, 50_002]
+        );
+    }
+
+    #[test]
+    fn diffs_long_outputs_that_differ_on_every_line() {
+        let left: Vec<String> = (0..2_000).map(|i| format!("left {}", i)).collect();
+        let right: Vec<String> = (0..2_000).map(|i| format!("right {}", i)).collect();
+        let (left_in_lcs, right_in_lcs) = lcs_indices(&left, &right);
+        assert!(!left_in_lcs.contains(&true) && !right_in_lcs.contains(&true));
+
+        // Besides the `---` / `+++` header, every line is removed and added.
+        let diff = unified_diff(&left.join("\n"), &right.join("\n"));
+        assert_eq!(
+            diff.lines().filter(|line| line.starts_with('-')).count(),
+            2_001
+        );
+        assert_eq!(
+            diff.lines().filter(|line| line.starts_with('+')).count(),
+            2_001
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+/// Error returned by the `Result`-returning sorting APIs, eg.
+/// [`try_sorted_value_with_config`](crate::try_sorted_value_with_config).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortError {
+    /// The Debug output couldn't be parsed, contains the parser's error message.
+    ParseFailed(String),
+    /// The Debug output is larger than [`SortConfig::max_input_bytes`](crate::SortConfig::max_input_bytes).
+    ///
+    /// Generating the Debug output is stopped as soon as it exceeds the limit, so this also
+    /// catches cyclic Debug implementations that would otherwise never finish.
+    InputTooLarge,
+    /// The Debug output is nested deeper than [`SortConfig::max_depth`](crate::SortConfig::max_depth).
+    DepthExceeded,
+    /// Sorting was re-entered too many times from within Debug implementations, eg. by a
+    /// type whose Debug implementation sorts itself.
+    RecursionLimitReached,
+}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SortError::ParseFailed(err) => write!(f, "failed to parse Debug output: {}", err),
+            SortError::InputTooLarge => {
+                write!(
+                    f,
+                    "Debug output exceeds the configured maximum input size \
+                     (possible cyclic Debug implementation)"
+                )
+            }
+            SortError::DepthExceeded => {
+                write!(
+                    f,
+                    "Debug output exceeds the configured maximum nesting depth"
+                )
+            }
+            SortError::RecursionLimitReached => {
+                write!(
+                    f,
+                    "sorting recursed too deeply through Debug implementations"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SortError {}
+
+/// The failure of a sorted assertion, with the sorted Debug output of both sides.
+///
+/// This is the panic payload of [`assert_eq_sorted_with`](crate::assert_eq_sorted_with) when
+/// [`SortConfig::structured_panic`](crate::SortConfig::structured_panic) is set. Its
+/// [`Display`](fmt::Display) output is the usual panic message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortAssertionError {
+    left: String,
+    right: String,
+    diff: String,
+    pub(crate) message: Option<String>,
+    assertion: Assertion,
+}
+
+/// The kind of assertion that failed, which decides how the failure is displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Assertion {
+    Eq,
+    /// [`assert_ne_sorted`](crate::assert_ne_sorted), whose sides are equal when it fails.
+    Ne,
+    /// [`assert_value_eq_sorted`](crate::assert_value_eq_sorted).
+    ValueEq,
+    /// [`assert_matches_sorted`](crate::assert_matches_sorted).
+    Matches,
+}
+
+impl Assertion {
+    /// The asserted expression, displayed in the first line of the failure.
+    fn expression(self) -> &'static str {
+        match self {
+            Assertion::Eq => "left == right",
+            Assertion::Ne => "left != right",
+            Assertion::ValueEq => "expected == actual",
+            Assertion::Matches => "value matches template",
+        }
+    }
+}
+
+impl SortAssertionError {
+    pub(crate) fn new(left: String, right: String, diff: String) -> Self {
+        Self {
+            left,
+            right,
+            diff,
+            message: None,
+            assertion: Assertion::Eq,
+        }
+    }
+
+    /// Marks the failure as one of `assertion` instead of [`assert_eq_sorted`](crate::assert_eq_sorted).
+    pub(crate) fn assertion(mut self, assertion: Assertion) -> Self {
+        self.assertion = assertion;
+        self
+    }
+
+    /// The failure of an assertion that expected both sides to differ. There's no diff
+    /// between equal sides, the shared output is displayed instead.
+    pub(crate) fn negated(left: String, right: String) -> Self {
+        Self::new(left, right, String::new()).assertion(Assertion::Ne)
+    }
+
+    /// The sorted Debug output of the left side.
+    pub fn left(&self) -> &str {
+        &self.left
+    }
+
+    /// The sorted Debug output of the right side.
+    pub fn right(&self) -> &str {
+        &self.right
+    }
+
+    /// The colored diff between both sides, including any extra sections (eg. moved
+    /// elements). Empty for [`assert_ne_sorted`](crate::assert_ne_sorted), whose sides
+    /// are equal.
+    pub fn diff(&self) -> &str {
+        &self.diff
+    }
+
+    /// The custom message passed to the assertion, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl fmt::Display for SortAssertionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "assertion failed: `({})`", self.assertion.expression())?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if self.assertion == Assertion::Ne {
+            write!(f, "\n\nBoth sides:\n{}\n", self.left)
+        } else {
+            write!(f, "\n\n{}\n", self.diff)
+        }
+    }
+}
+
+impl std::error::Error for SortAssertionError {}
@@ -0,0 +1,45 @@
+//! Global hook for observing sorted assertions, eg. to collect metrics.
+use std::sync::{Arc, RwLock};
+
+/// Called after each sorted assertion with whether both sides matched and the combined size
+/// of their Debug output in bytes, see [`set_on_compare`].
+pub type CompareHookFn = Box<dyn Fn(bool, usize) + Send + Sync>;
+
+static ON_COMPARE: RwLock<Option<Arc<CompareHookFn>>> = RwLock::new(None);
+
+/// Installs a hook that's called after each sorted assertion (eg.
+/// [`assert_eq_sorted`](crate::assert_eq_sorted) or
+/// [`assert_value_eq_sorted`](crate::assert_value_eq_sorted)) with whether both sides
+/// matched and the combined size of their Debug output in bytes.
+///
+/// The hook replaces any previously installed hook and applies to all threads until it's
+/// removed with [`clear_on_compare`]. Without a hook, nothing extra is computed.
+///
+/// ```rust
+/// use pretty_assertions_sorted::{clear_on_compare, set_on_compare};
+///
+/// set_on_compare(Box::new(|matched, input_bytes| {
+///     eprintln!("sorted assertion (matched: {}, {} bytes)", matched, input_bytes);
+/// }));
+/// clear_on_compare();
+/// ```
+pub fn set_on_compare(hook: CompareHookFn) {
+    *ON_COMPARE.write().unwrap_or_else(|err| err.into_inner()) = Some(Arc::new(hook));
+}
+
+/// Removes the hook installed with [`set_on_compare`], if any.
+pub fn clear_on_compare() {
+    *ON_COMPARE.write().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// Calls the installed hook, if any. `input_bytes` is only evaluated when there's a hook.
+pub(crate) fn on_compare(matched: bool, input_bytes: impl FnOnce() -> usize) {
+    // The lock isn't held while calling the hook, so it can replace or remove itself.
+    let hook = ON_COMPARE
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    if let Some(hook) = hook {
+        hook(matched, input_bytes());
+    }
+}
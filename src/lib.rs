@@ -25,17 +25,45 @@
 //!
 //! `assert_eq` is provided as a re-export of `pretty_assertions::assert_eq` and should
 //! be used if you don't want the Debug output to be sorted, or if the Debug output can't
-//! be sorted (not supported types, eg. f64::NEG_INFINITY, or custom Debug output).
+//! be sorted (eg. custom Debug output that isn't supported).
+//!
+//! By default only maps and sets are sorted, since their order isn't meaningful. Lists and
+//! tuples (eg. a `Vec`) keep their order, see `SortConfig::sort_sequences` to sort them too.
 //!
 //! ## Tip
 //!
 //! Specify it as [`[dev-dependencies]`](http://doc.crates.io/specifying-dependencies.html#development-dependencies)
 //! and it will only be used for compiling tests, examples, and benchmarks.
 //! This way the compile time of `cargo build` won't be affected!
-use std::fmt;
+use std::cell::Cell;
+use std::fmt::{self, Write};
+use std::io;
+
+mod config;
+mod diff;
+mod error;
+mod hook;
+mod migration;
+mod normalize;
+mod parse;
+mod sort;
+mod template;
 
-use darrentsung_debug_parser::*;
+pub use config::{
+    ComparatorFn, EntryFilterFn, FieldKind, KeyTransformFn, SortConfig, ValidateFn, VariantRankFn,
+};
+pub use darrentsung_debug_parser::{
+    IdentValue, KeyValue, List, Map, OrNonExhaustive, Set, Struct, Term, Tuple, Value,
+};
+use error::Assertion;
+pub use error::{SortAssertionError, SortError};
+pub use hook::{clear_on_compare, set_on_compare, CompareHookFn};
+pub use migration::Migration;
+use normalize::normalize;
+use parse::parse;
+pub use parse::ParseError;
 pub use pretty_assertions::{assert_eq, assert_ne, Comparison};
+use sort::sort_maps;
 
 /// This is a wrapper with similar functionality to [`assert_eq`], however, the
 /// [`Debug`] representation is sorted to provide deterministic output.
@@ -44,40 +72,286 @@ pub use pretty_assertions::{assert_eq, assert_ne, Comparison};
 /// custom [`Debug`] implementations that don't conform to the format that #[derive(Debug)]
 /// uses, eg. `fmt.debug_struct()`, `fmt.debug_map()`, etc.
 ///
-/// Don't use this if you want to test the ordering of the types that are sorted, since
-/// sorting will clobber any previous ordering.
+/// Only maps and sets (eg. `HashMap` / `HashSet`) are sorted, lists and tuples keep their
+/// order unless [`SortConfig::sort_sequences`] is enabled. Don't use this if you want to
+/// test the ordering of the types that are sorted, since sorting will clobber any previous
+/// ordering.
+///
+/// A map can be compared against a non-empty list of `(key, value)` pairs, eg. an array
+/// literal. An empty list has no pairs to tell it apart from any other list, so it doesn't
+/// equal an empty map:
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted;
+/// use std::collections::HashMap;
 ///
-/// Potential use-cases that aren't implemented yet:
-/// * Blocklist for field names that shouldn't be sorted
-/// * Sorting more than just maps (struct fields, lists, etc.)
+/// let map: HashMap<_, _> = [("b", 2), ("a", 1)].into_iter().collect();
+/// assert_eq_sorted!(map, [("a", 1), ("b", 2)]);
+/// ```
+///
+/// Only the Debug output is compared, so the types of both sides don't need to match, eg.
+/// a `HashMap<&str, _>` equals a `HashMap<String, _>` with the same contents.
+///
+/// Both operands are evaluated (left first) before anything is parsed, see
+/// [`assert_eq_sorted_lazy`] to avoid computing an expensive right-hand side.
 #[macro_export]
 macro_rules! assert_eq_sorted {
     ($left:expr, $right:expr$(,)?) => ({
-        $crate::assert_eq_sorted!(@ $left, $right, "", "");
+        $crate::assert_eq_sorted!(@ $left, $right, "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted!(@ $left, $right, $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let config = $crate::SortConfig::default();
+                if let ::core::option::Option::Some(err) =
+                    $crate::__sorted_comparison(left_val, right_val, &config)
+                {
+                    $crate::__fail_assertion(&config, err, ::std::format!($($arg)*))
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the sorted Debug output of both sides differs, the counterpart of
+/// [`assert_eq_sorted`].
+///
+/// Unlike [`assert_ne`], values that only differ in the order of their maps are considered
+/// equal, so the assertion can't pass because of a different iteration order.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_ne_sorted;
+/// use std::collections::HashMap;
+///
+/// let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = [("a", 1), ("b", 3)].into_iter().collect();
+/// assert_ne_sorted!(left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_ne_sorted {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_ne_sorted!(@ $left, $right, "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_ne_sorted!(@ $left, $right, $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let config = $crate::SortConfig::default();
+                if let ::core::option::Option::Some(err) =
+                    $crate::__sorted_match(left_val, right_val, &config)
+                {
+                    $crate::__fail_assertion(&config, err, ::std::format!($($arg)*))
+                }
+            }
+        }
+    });
+}
+
+/// Like [`assert_eq_sorted`], but the right-hand side is a closure that's only called once
+/// the Debug output of the left-hand side has been parsed successfully.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_lazy;
+/// use std::collections::HashMap;
+///
+/// let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+/// assert_eq_sorted_lazy!(left, || [("b", 2), ("a", 1)].into_iter().collect::<HashMap<_, _>>());
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_lazy {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_lazy!(@ $left, $right, "");
+    });
+    ($left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_lazy!(@ $left, $right, $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $($arg:tt)*) => ({
+        match &($left) {
+            left_val => {
+                let config = $crate::SortConfig::default();
+                $crate::__ensure_sortable(left_val, &config);
+                let right_val = ($right)();
+                if let ::core::option::Option::Some(err) =
+                    $crate::__sorted_comparison(left_val, &right_val, &config)
+                {
+                    $crate::__fail_assertion(&config, err, ::std::format!($($arg)*))
+                }
+            }
+        }
+    });
+}
+
+/// Like [`assert_eq_sorted`], but normalizes and sorts both sides according to the
+/// provided [`SortConfig`].
+///
+/// ```rust
+/// use pretty_assertions_sorted::{assert_eq_sorted_with, SortConfig};
+/// use std::collections::HashMap;
+///
+/// let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = [("a", 10), ("b", 20)].into_iter().collect();
+/// assert_eq_sorted_with!(SortConfig::new().keys_only(true), left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_with {
+    ($config:expr, $left:expr, $right:expr$(,)?) => ({
+        $crate::assert_eq_sorted_with!(@ $config, $left, $right, "");
+    });
+    ($config:expr, $left:expr, $right:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_with!(@ $config, $left, $right, $($arg)+);
+    });
+    (@ $config:expr, $left:expr, $right:expr, $($arg:tt)*) => ({
+        match (&($config), &($left), &($right)) {
+            (config, left_val, right_val) => {
+                if let ::core::option::Option::Some(err) =
+                    $crate::__sorted_comparison(left_val, right_val, config)
+                {
+                    $crate::__fail_assertion(config, err, ::std::format!($($arg)*))
+                }
+            }
+        }
+    });
+}
+
+/// Like [`assert_eq_sorted`], but only compares the keys of maps, ignoring their values
+/// entirely, see [`SortConfig::keys_only`]. Useful for testing the shape of a value.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_keys_eq_sorted;
+/// use std::collections::HashMap;
+///
+/// let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = [("b", 20), ("a", 10)].into_iter().collect();
+/// assert_keys_eq_sorted!(left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_keys_eq_sorted {
+    ($left:expr, $right:expr$(,)?) => ({
+        $crate::assert_keys_eq_sorted!(@ $left, $right, "");
     });
     ($left:expr, $right:expr, $($arg:tt)*) => ({
-        $crate::assert_eq_sorted!(@ $left, $right, ": ", $($arg)+);
+        $crate::assert_keys_eq_sorted!(@ $left, $right, $($arg)+);
+    });
+    (@ $left:expr, $right:expr, $($arg:tt)*) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                let config = $crate::SortConfig::new().keys_only(true);
+                if let ::core::option::Option::Some(err) =
+                    $crate::__sorted_comparison(left_val, right_val, &config)
+                {
+                    $crate::__fail_assertion(&config, err, ::std::format!($($arg)*))
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that two expressions differ in at most `$max_diffs` places after sorting, eg.
+/// changed, added or removed map entries, see [`SortConfig::max_differences`]. Fails
+/// listing all differences otherwise.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_eq_sorted_within;
+/// use std::collections::HashMap;
+///
+/// let left: HashMap<_, _> = [("a", 1), ("b", 2), ("c", 3)].into_iter().collect();
+/// let right: HashMap<_, _> = [("c", 3), ("b", 20), ("a", 1)].into_iter().collect();
+/// assert_eq_sorted_within!(left, right, 1);
+/// ```
+#[macro_export]
+macro_rules! assert_eq_sorted_within {
+    ($left:expr, $right:expr, $max_diffs:expr$(,)?) => ({
+        $crate::assert_eq_sorted_within!(@ $left, $right, $max_diffs, "");
+    });
+    ($left:expr, $right:expr, $max_diffs:expr, $($arg:tt)*) => ({
+        $crate::assert_eq_sorted_within!(@ $left, $right, $max_diffs, $($arg)+);
     });
-    (@ $left:expr, $right:expr, $maybe_semicolon:expr, $($arg:tt)*) => ({
+    (@ $left:expr, $right:expr, $max_diffs:expr, $($arg:tt)*) => ({
         match (&($left), &($right)) {
             (left_val, right_val) => {
-                let left_val = $crate::SortedDebug::new(left_val);
-                let right_val = $crate::SortedDebug::new(right_val);
-                
-                if !(format!("{:?}", left_val) == format!("{:?}", right_val)) {
-                    // We create the comparison string outside the panic! call
-                    // because creating the comparison string could panic itself.
-                    let comparison_string = $crate::Comparison::new(
-                        &left_val,
-                        &right_val
-                    ).to_string();
-                    ::core::panic!("assertion failed: `(left == right)`{}{}\
-                       \n\
-                       \n{}\
-                       \n",
-                       $maybe_semicolon,
-                       format_args!($($arg)*),
-                       comparison_string,
+                let config = $crate::SortConfig::new().max_differences($max_diffs);
+                if let ::core::option::Option::Some(err) =
+                    $crate::__sorted_comparison(left_val, right_val, &config)
+                {
+                    $crate::__fail_assertion(&config, err, ::std::format!($($arg)*))
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the sorted [`Value`] tree of `$actual` is structurally equal to
+/// `$expected`, a [`Value`] (eg. one returned from [`sorted_value`], or one constructed by
+/// hand).
+///
+/// The expected tree is compared as-is, so hand-constructed trees should list map entries
+/// in sorted order.
+#[macro_export]
+macro_rules! assert_value_eq_sorted {
+    ($expected:expr, $actual:expr$(,)?) => ({
+        $crate::assert_value_eq_sorted!(@ $expected, $actual, "");
+    });
+    ($expected:expr, $actual:expr, $($arg:tt)*) => ({
+        $crate::assert_value_eq_sorted!(@ $expected, $actual, $($arg)+);
+    });
+    (@ $expected:expr, $actual:expr, $($arg:tt)*) => ({
+        match (&($expected), &($actual)) {
+            (expected_val, actual_val) => {
+                let expected_val: &$crate::Value = expected_val;
+                if let ::core::option::Option::Some(err) =
+                    $crate::__value_mismatch(expected_val, actual_val)
+                {
+                    $crate::__fail_assertion(
+                        &$crate::SortConfig::default(),
+                        err,
+                        ::std::format!($($arg)*),
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Asserts that the sorted Debug output of `$value` (as displayed by [`SortedDebug`])
+/// matches `$template`, where `{{regex}}` placeholders match dynamic parts like generated
+/// ids and the rest has to match exactly.
+///
+/// Placeholders support a subset of regex syntax: literals, `.`, character classes,
+/// `\d`, `\w`, `\s`, groups with alternation and the quantifiers `*`, `+`, `?`, `{n}`,
+/// `{n,}` and `{n,m}`. Anchors, lookarounds, backreferences and lazy quantifiers aren't
+/// supported. Panics if a placeholder isn't valid. Outside of placeholders, `{{{{` matches
+/// a literal `{{`.
+///
+/// ```rust
+/// use pretty_assertions_sorted::assert_matches_sorted;
+/// use std::collections::HashMap;
+///
+/// let ids: HashMap<_, _> = [("b", 20), ("a", 17)].into_iter().collect();
+/// assert_matches_sorted!(ids, "{\n    \"a\": {{\\d+}},\n    \"b\": {{\\d+}},\n}");
+/// ```
+#[macro_export]
+macro_rules! assert_matches_sorted {
+    ($value:expr, $template:expr$(,)?) => ({
+        $crate::assert_matches_sorted!(@ $value, $template, "");
+    });
+    ($value:expr, $template:expr, $($arg:tt)*) => ({
+        $crate::assert_matches_sorted!(@ $value, $template, $($arg)+);
+    });
+    (@ $value:expr, $template:expr, $($arg:tt)*) => ({
+        match (&($value), &($template)) {
+            (value, template) => {
+                if let ::core::option::Option::Some(err) =
+                    $crate::__template_mismatch(value, template)
+                {
+                    $crate::__fail_assertion(
+                        &$crate::SortConfig::default(),
+                        err,
+                        ::std::format!($($arg)*),
                     )
                 }
             }
@@ -91,459 +365,4193 @@ macro_rules! assert_eq_sorted {
 /// This works through parsing the output and sorting the `debug_map()` type.
 ///
 /// DISCLAIMER: This Debug implementation will panic if the inner value's Debug
-/// representation can't be sorted. This is used to notify users when used in tests. An
-/// alternative solution of falling back to non-sorted could be implemented.
+/// representation can't be sorted. This is used to notify users when used in tests. Use
+/// [`SortedDebug::new_lenient`] to fall back to the unsorted output instead.
 ///
-/// Potential use-cases that aren't implemented yet:
-/// * Blocklist for field names that shouldn't be sorted
-/// * Sorting more than just maps (struct fields, lists, etc.)
-pub struct SortedDebug<T>(T);
+/// It can be used from within other Debug implementations. If sorting recurses too deeply
+/// (eg. a type whose Debug implementation sorts itself), the innermost value is displayed
+/// as `<recursive>` instead of overflowing the stack.
+pub struct SortedDebug<T> {
+    value: T,
+    config: SortConfig,
+}
 
 impl<T> SortedDebug<T> {
     pub fn new(v: T) -> Self {
-        Self(v)
+        Self::with_config(v, SortConfig::default())
+    }
+
+    /// Creates a wrapper that displays the original `{:#?}` output unchanged when it can't
+    /// be parsed, instead of panicking.
+    pub fn new_lenient(v: T) -> Self {
+        Self::with_config(v, SortConfig::new().lenient(true))
+    }
+
+    /// Creates a wrapper that keeps the collections in the given fields (or string map keys)
+    /// in their original order, see [`SortConfig::blocklist`].
+    pub fn with_blocklist(v: T, fields: &[&str]) -> Self {
+        Self::with_config(v, SortConfig::new().blocklist(fields))
+    }
+
+    /// Creates a wrapper that normalizes and sorts the Debug output according to `config`.
+    pub fn with_config(v: T, config: SortConfig) -> Self {
+        Self { value: v, config }
+    }
+
+    /// Orders map keys and set elements with `comparator`, see [`SortConfig::comparator`].
+    ///
+    /// ```rust
+    /// use pretty_assertions_sorted::SortedDebug;
+    /// use std::collections::HashSet;
+    ///
+    /// let words = HashSet::from(["ccc", "a", "bb"]);
+    /// let by_length = SortedDebug::new(&words).with_comparator(Box::new(|a, b| {
+    ///     format!("{:?}", a).len().cmp(&format!("{:?}", b).len())
+    /// }));
+    /// assert_eq!(format!("{:?}", by_length), "{\n    \"a\",\n    \"bb\",\n    \"ccc\",\n}");
+    /// ```
+    pub fn with_comparator(self, comparator: ComparatorFn) -> Self {
+        Self {
+            value: self.value,
+            config: self.config.comparator(comparator),
+        }
+    }
+}
+
+impl<'a> SortedDebug<DebugFields<'a>> {
+    /// Creates a wrapper around an ad-hoc struct named `name` with the given fields, for
+    /// composing an expected structure without a real type.
+    ///
+    /// ```
+    /// use pretty_assertions_sorted::SortedDebug;
+    /// use std::collections::HashSet;
+    ///
+    /// let tags = HashSet::from(["b", "a"]);
+    /// let fields: [(&str, &dyn std::fmt::Debug); 2] = [("id", &1), ("tags", &tags)];
+    /// let expected = SortedDebug::from_fields("User", &fields);
+    /// assert_eq!(
+    ///     format!("{:?}", expected),
+    ///     "User {\n    id: 1,\n    tags: {\n        \"a\",\n        \"b\",\n    },\n}",
+    /// );
+    /// ```
+    pub fn from_fields(name: &'a str, fields: &'a [(&'a str, &'a dyn fmt::Debug)]) -> Self {
+        Self::new(DebugFields { name, fields })
+    }
+}
+
+/// A struct assembled from named Debug values, see [`SortedDebug::from_fields`].
+pub struct DebugFields<'a> {
+    name: &'a str,
+    fields: &'a [(&'a str, &'a dyn fmt::Debug)],
+}
+
+impl fmt::Debug for DebugFields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = f.debug_struct(self.name);
+        for (name, value) in self.fields {
+            s.field(name, value);
+        }
+        s.finish()
     }
 }
 
 impl<T: fmt::Debug> fmt::Debug for SortedDebug<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut value = match parse(&format!("{:?}", self.0)) {
-            Ok(value) => value,
-            Err(err) => {
-                ::core::panic!("Failed to parse Debug output for sorting (please use `assert_eq!` instead and/or file an issue for your use-case)!\nError: {}", err)
-            }
+        let value = match try_parsed_value(&self.value, &self.config) {
+            Ok((value, _)) => value,
+            // Formatting the value would recurse again, display a placeholder instead.
+            Err(SortError::RecursionLimitReached) => return f.write_str("<recursive>"),
+            Err(_) if self.config.lenient => return write!(f, "{:#?}", self.value),
+            Err(err) => panic_on_sort_error(err),
         };
-
-        sort_maps(&mut value);
-
-        // Replace one-line non-exhaustive objects with empty brackets separated by
-        // newlines. This changes output like: "Foo { .. }" with "Foo {\n}". "Foo {\n}" is
-        // more desirable because it diffs better against some multi-line output of "Foo {
-        // value: 10.0 }" (imagine the newlines please).
-        let formatted_output = format!("{:#?}", value).replace("{ .. }", "{\n}");
-        fmt::Display::fmt(&formatted_output, f)
+        fmt::Display::fmt(&sorted_output(value, &self.config), f)
     }
 }
 
-fn sort_maps(v: &mut Value) {
-    match v {
-        Value::Struct(s) => {
-            for ident_value_or_non_exhaustive in &mut s.values {
-                match ident_value_or_non_exhaustive {
-                    OrNonExhaustive::Value(ident_value) => {
-                        sort_maps(&mut ident_value.value);
-                    }
-                    OrNonExhaustive::NonExhaustive => (),
-                }
-            }
-        }
-        Value::Set(s) => {
-            s.values.sort_by(|a, b| a.cmp(&b));
-            for child_v in &mut s.values {
-                sort_maps(child_v);
-            }
-        }
-        Value::Map(map) => {
-            map.values.sort_by(|a, b| a.key.cmp(&b.key));
+/// Normalizes, sorts and renders a parsed [`Value`] tree. Both [`SortedDebug`] and
+/// [`sort_debug_string`] display values through this, so their output is identical.
+fn sorted_output(mut value: Value, config: &SortConfig) -> String {
+    normalize(&mut value, config);
+    sort_maps(&mut value, config);
+    render_for_display(&mut value, config)
+}
 
-            for key_value in &mut map.values {
-                sort_maps(&mut key_value.key);
-                sort_maps(&mut key_value.value);
+/// Renders a sorted [`Value`] tree the way [`SortedDebug`] displays it.
+fn render(value: &Value) -> String {
+    // Replace one-line non-exhaustive objects with empty brackets separated by
+    // newlines. This changes output like: "Foo { .. }" with "Foo {\n}". "Foo {\n}" is
+    // more desirable because it diffs better against some multi-line output of "Foo {
+    // value: 10.0 }" (imagine the newlines please).
+    //
+    // String terms are left untouched, even if they contain "{ .. }".
+    let output = format!("{:#?}", value);
+    let mut rendered = String::with_capacity(output.len());
+    let mut rest = output.as_str();
+    let mut in_string = false;
+    while let Some(c) = rest.chars().next() {
+        if !in_string {
+            if let Some(after) = rest.strip_prefix("{ .. }") {
+                rendered.push_str("{\n}");
+                rest = after;
+                continue;
             }
-        }
-        Value::List(l) => {
-            l.values.sort_by(|a, b| a.cmp(&b));
-            for child_v in &mut l.values {
-                sort_maps(child_v);
+            // A quote char term doesn't start a string.
+            if let Some(after) = rest.strip_prefix("'\"'") {
+                rendered.push_str("'\"'");
+                rest = after;
+                continue;
             }
         }
-        Value::Tuple(t) => {
-            t.values.sort_by(|a, b| a.cmp(&b));
-            for child_v in &mut t.values {
-                sort_maps(child_v);
+        match c {
+            '"' => in_string = !in_string,
+            '\\' if in_string => {
+                // Copy the escaped character as well, so escaped quotes don't end the string.
+                let escaped_len = rest[1..].chars().next().map_or(0, char::len_utf8);
+                rendered.push_str(&rest[..1 + escaped_len]);
+                rest = &rest[1 + escaped_len..];
+                continue;
             }
+            _ => (),
         }
-        // No need to recurse for Term variant.
-        Value::Term(_) => (),
+        rendered.push(c);
+        rest = &rest[c.len_utf8()..];
     }
+    rendered
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use indoc::indoc;
-    use std::assert_eq;
-    use std::collections::HashMap;
-
-    const TEST_RERUNS_FOR_DETERMINISM: u32 = 100;
-
-    fn sorted_debug<T: fmt::Debug>(v: T) -> String {
-        format!("{:#?}", SortedDebug(v))
+/// Renders a sorted [`Value`] tree, truncated according to `config`.
+fn render_for_display(value: &mut Value, config: &SortConfig) -> String {
+    if let Some(max_term_len) = config.max_term_len {
+        normalize::truncate_terms(value, max_term_len);
     }
-
-    #[test]
-    fn noop_sorts() {
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            assert_eq!(sorted_debug(2), "2");
+    let output = render(value);
+    match config.max_output_lines {
+        Some(max_output_lines) if output.lines().count() > max_output_lines => {
+            let mut lines: Vec<&str> = output.lines().take(max_output_lines).collect();
+            lines.push("... (truncated)");
+            lines.join("\n")
         }
+        _ => output,
     }
+}
 
-    #[test]
-    fn sorts_hashmap() {
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            // Note that we have to create the HashMaps each try
-            // in order to induce non-determinism in the debug output.
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(1, true);
-                map.insert(2, true);
-                map.insert(20, true);
-                map
-            };
+/// Already rendered output, displayed verbatim when formatted with Debug.
+struct Rendered<'a>(&'a str);
 
-            let expected = indoc!(
-                "{
-                    1: true,
-                    2: true,
-                    20: true,
-                }"
-            );
-            assert_eq!(sorted_debug(item), expected);
-        }
+impl fmt::Debug for Rendered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
     }
+}
 
-    #[test]
-    fn sorts_hashmaps_with_non_exhaustive_object_values() {
-        #[allow(unused)]
-        struct Foo {
-            value: f32,
-        }
+/// Sorts both sides according to `config` and returns the assertion failure if they
+/// differ. Used by the assertion macros, not public API.
+#[doc(hidden)]
+#[track_caller]
+pub fn __sorted_comparison<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+    config: &SortConfig,
+) -> Option<SortAssertionError> {
+    observed_comparison(left, right, config).err()
+}
 
-        impl fmt::Debug for Foo {
-            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.debug_struct("Foo")
-                    .field("value", &self.value)
-                    .finish_non_exhaustive()
-            }
+/// Sorts both sides according to `config` and returns the assertion failure if they
+/// match. Only the rendered sides are compared and no diff is built, the failure shows
+/// the shared output instead. Used by [`assert_ne_sorted`], not public API.
+#[doc(hidden)]
+#[track_caller]
+pub fn __sorted_match<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+    config: &SortConfig,
+) -> Option<SortAssertionError> {
+    let (left_output, right_output, input_bytes) = match sorted_values(left, right, config) {
+        Some((left_value, right_value, input_bytes)) => {
+            (render(&left_value), render(&right_value), Some(input_bytes))
         }
+        None => (format!("{:#?}", left), format!("{:#?}", right), None),
+    };
+    let matched = left_output == right_output;
+    hook::on_compare(matched, || {
+        input_bytes.unwrap_or_else(|| format!("{:?}", left).len() + format!("{:?}", right).len())
+    });
+    matched.then(|| SortAssertionError::negated(left_output, right_output))
+}
 
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(1, Foo { value: 10.1 });
-                map.insert(32, Foo { value: 2.0 });
-                map.insert(2, Foo { value: -1.5 });
-                map
-            };
+/// Like [`sorted_comparison`], but reports the comparison to the hook installed with
+/// [`set_on_compare`].
+#[track_caller]
+fn observed_comparison<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+    config: &SortConfig,
+) -> Result<String, SortAssertionError> {
+    let result = sorted_comparison(left, right, config);
+    hook::on_compare(result.is_ok(), || {
+        format!("{:?}", left).len() + format!("{:?}", right).len()
+    });
+    result
+}
 
-            let expected = indoc!(
-                "{
-                    1: Foo {
-                        value: 10.1,
-                        ..
-                    },
-                    2: Foo {
-                        value: -1.5,
-                        ..
-                    },
-                    32: Foo {
-                        value: 2.0,
-                        ..
-                    },
-                }"
-            );
-            assert_eq!(sorted_debug(item), expected);
+/// Sorts both sides according to `config` and returns the assertion failure if they
+/// differ, or their shared sorted output if they match. Each side is formatted and sorted
+/// only once.
+#[track_caller]
+fn sorted_comparison<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+    config: &SortConfig,
+) -> Result<String, SortAssertionError> {
+    let Some((mut left_value, mut right_value, _)) = sorted_values(left, right, config) else {
+        return unsorted_comparison(left, right);
+    };
+    if let Some(validate) = &config.validate {
+        for (side, value) in [("left", &left_value), ("right", &right_value)] {
+            if let Err(err) = validate(value) {
+                return Err(SortAssertionError::new(
+                    render(&left_value),
+                    render(&right_value),
+                    format!("validation of the {} side failed: {}", side, err),
+                ));
+            }
         }
     }
+    if config.first_diff_only {
+        return match diff::first_difference(&left_value, &right_value) {
+            Some(difference) => Err(SortAssertionError::new(
+                render(&left_value),
+                render(&right_value),
+                difference.to_string(),
+            )),
+            None => Ok(render(&left_value)),
+        };
+    }
 
-    #[test]
-    fn sorts_list() {
-        #[allow(unused)]
-        #[derive(Debug)]
-        struct Foo {
-            value: f32,
+    let allowed_differences = config
+        .max_differences
+        .map(|max| (max, diff::differences(&left_value, &right_value)));
+    if let Some((max, differences)) = &allowed_differences {
+        if differences.len() <= *max {
+            return Ok(render(&left_value));
         }
+    }
 
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = Vec::new();
-                map.insert(0, Foo { value: 10.1 });
-                map.insert(1, Foo { value: 2.0 });
-                map.insert(2, Foo { value: -1.5 });
-                map
-            };
+    let (mut left_output, mut right_output) = (render(&left_value), render(&right_value));
+    if left_output == right_output {
+        return Ok(left_output);
+    }
 
-            let expected = indoc!(
-                "[
-                    Foo {
-                        value: -1.5,
-                    },
-                    Foo {
-                        value: 10.1,
-                    },
-                    Foo {
-                        value: 2.0,
-                    },
-                ]"
+    // Extra information about the differences, displayed after the diff.
+    let mut sections = String::new();
+    if config.detect_moves {
+        let moves = diff::list_moves(&left_value, &right_value);
+        append_section(&mut sections, "Moved elements:", moves);
+    }
+    if config.pair_list_elements {
+        let differences = diff::differences(&left_value, &right_value);
+        append_section(
+            &mut sections,
+            "Differences between paired elements:",
+            differences.iter().map(diff::Difference::summary),
+        );
+    }
+    if let Some((max, differences)) = allowed_differences {
+        append_section(
+            &mut sections,
+            &format!(
+                "{} differences, at most {} allowed:",
+                differences.len(),
+                max
+            ),
+            differences.iter().map(diff::Difference::summary),
+        );
+    }
+    if config.report_original_positions {
+        if let (Ok(left_unsorted), Ok(right_unsorted)) = (
+            try_normalized_value(left, config),
+            try_normalized_value(right, config),
+        ) {
+            let unmatched_elements = diff::unmatched_elements(
+                (&left_value, &left_unsorted),
+                (&right_value, &right_unsorted),
+                config,
+            );
+            append_section(
+                &mut sections,
+                "Differing elements at their original (unsorted) positions:",
+                unmatched_elements,
             );
-            let sorted = sorted_debug(item);
-            println!("{}", sorted);
-            // let comparison = Comparison::new(
-            //     expected,
-            //     sorted.as_str()
-            // ).to_string();
-            println!("{}", expected);
-            assert_eq!(sorted, expected);
         }
     }
 
-    #[test]
-    fn test_list_assert_eq_sorted() {
-        #[allow(unused)]
-        #[derive(Debug, PartialEq)]
+    // Truncation only affects what's displayed, equality was decided on the full content.
+    if config.max_term_len.is_some() || config.max_output_lines.is_some() {
+        left_output = render_for_display(&mut left_value, config);
+        right_output = render_for_display(&mut right_value, config);
+    }
+
+    // Overview displayed before the diff. Differently named types with the same fields are
+    // easy to miss in the line diff, so they're reported here as well.
+    let mut header = String::new();
+    if config.summary {
+        let differences = diff::differences(&left_value, &right_value);
+        write!(header, "{}\n\n", diff::Summary::new(&differences))
+            .expect("writing to a String can't fail");
+    }
+    for mismatch in diff::type_mismatches(&left_value, &right_value) {
+        write!(header, "{}\n\n", mismatch).expect("writing to a String can't fail");
+    }
+
+    let grouped_diff = config
+        .group_by_top_key
+        .then(|| grouped_diff(&left_value, &right_value, config))
+        .flatten();
+    let diff = if let Some(grouped_diff) = grouped_diff {
+        format!("{}{}{}", header, grouped_diff, sections)
+    } else if config.side_by_side {
+        format!(
+            "{}{}{}",
+            header,
+            diff::side_by_side(&left_output, &right_output),
+            sections
+        )
+    } else if config.unified_diff {
+        format!(
+            "{}{}{}",
+            header,
+            diff::unified_diff(&left_output, &right_output),
+            sections
+        )
+    } else {
+        format!(
+            "{}{}{}",
+            header,
+            Comparison::new(&Rendered(&left_output), &Rendered(&right_output)),
+            sections
+        )
+    };
+    Err(SortAssertionError::new(left_output, right_output, diff))
+}
+
+/// Normalizes and sorts both sides for comparing them, along with the combined length of
+/// their Debug output. Returns `None` if they should be compared unsorted (see
+/// [`SortConfig::exact`] and [`SortConfig::lenient`]).
+#[track_caller]
+fn sorted_values<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+    config: &SortConfig,
+) -> Option<(Value, Value, usize)> {
+    if config.exact {
+        return None;
+    }
+    let ((mut left_value, left_len), (mut right_value, right_len)) = match (
+        try_parsed_value(left, config),
+        try_parsed_value(right, config),
+    ) {
+        (Ok(left), Ok(right)) => (left, right),
+        _ if config.lenient => return None,
+        (Err(err), _) | (_, Err(err)) => panic_on_sort_error(err),
+    };
+    normalize(&mut left_value, config);
+    normalize(&mut right_value, config);
+    normalize::maps_from_pairs(&mut left_value, &mut right_value);
+    sort_maps(&mut left_value, config);
+    sort_maps(&mut right_value, config);
+    if let Some(default) = &config.default_for_missing {
+        normalize::fill_missing_keys(&mut left_value, &mut right_value, default, config);
+    }
+    if config.float_tolerance.is_some() {
+        normalize::apply_float_tolerance(&mut left_value, &mut right_value, config);
+    }
+    Some((left_value, right_value, left_len + right_len))
+}
+
+/// Panics like the assertion macros would if `v` can't be sorted. Used by
+/// [`assert_eq_sorted_lazy`], not public API.
+#[doc(hidden)]
+#[track_caller]
+pub fn __ensure_sortable<T: fmt::Debug + ?Sized>(v: &T, config: &SortConfig) {
+    if let Err(err) = try_sorted_value_with_config(v, config) {
+        if !config.lenient {
+            panic_on_sort_error(err);
+        }
+    }
+}
+
+/// Panics with `err`, either formatted or as a structured payload depending on `config`.
+/// The panic is reported at the location of the assertion. Used by the assertion macros,
+/// not public API.
+#[doc(hidden)]
+#[track_caller]
+pub fn __fail_assertion(config: &SortConfig, mut err: SortAssertionError, message: String) -> ! {
+    if !message.is_empty() {
+        err.message = Some(message);
+    }
+    if config.structured_panic {
+        std::panic::panic_any(err)
+    }
+    ::core::panic!("{}", err)
+}
+
+/// Sorts `actual` and returns the assertion failure if its tree differs from `expected`.
+/// Used by [`assert_value_eq_sorted`], not public API.
+#[doc(hidden)]
+#[track_caller]
+pub fn __value_mismatch<T: fmt::Debug + ?Sized>(
+    expected: &Value,
+    actual: &T,
+) -> Option<SortAssertionError> {
+    let config = SortConfig::default();
+    let (mut actual_value, actual_bytes) =
+        try_parsed_value(actual, &config).unwrap_or_else(|err| panic_on_sort_error(err));
+    normalize(&mut actual_value, &config);
+    sort_maps(&mut actual_value, &config);
+
+    let matched = *expected == actual_value;
+    hook::on_compare(matched, || format!("{:?}", expected).len() + actual_bytes);
+    if matched {
+        return None;
+    }
+    let diff = Comparison::new(expected, &actual_value).to_string();
+    let err = SortAssertionError::new(render(expected), render(&actual_value), diff);
+    Some(err.assertion(Assertion::ValueEq))
+}
+
+/// Returns the assertion failure if the sorted Debug output of `value` doesn't match
+/// `template`. Used by [`assert_matches_sorted`], not public API.
+#[doc(hidden)]
+#[track_caller]
+pub fn __template_mismatch<T: fmt::Debug + ?Sized>(
+    value: &T,
+    template: &str,
+) -> Option<SortAssertionError> {
+    let config = SortConfig::default();
+    let (parsed, input_bytes) =
+        try_parsed_value(value, &config).unwrap_or_else(|err| panic_on_sort_error(err));
+    let actual = sorted_output(parsed, &config);
+    let matched = match template::matches_template(template, &actual) {
+        Ok(matched) => matched,
+        Err(err) => ::core::panic!("invalid template placeholder: {}", err),
+    };
+    hook::on_compare(matched, || template.len() + input_bytes);
+    if matched {
+        return None;
+    }
+    let diff = Comparison::new(&Rendered(template), &Rendered(&actual)).to_string();
+    let err = SortAssertionError::new(template.to_string(), actual, diff);
+    Some(err.assertion(Assertion::Matches))
+}
+
+/// Compares the unsorted Debug output of `left` and `right`, like
+/// [`pretty_assertions::assert_eq`].
+fn unsorted_comparison<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+) -> Result<String, SortAssertionError> {
+    let (left, right) = (format!("{:#?}", left), format!("{:#?}", right));
+    if left == right {
+        return Ok(left);
+    }
+    let diff = Comparison::new(&Rendered(&left), &Rendered(&right)).to_string();
+    Err(SortAssertionError::new(left, right, diff))
+}
+
+/// Diffs each differing top-level entry of `left` and `right` separately, under a header
+/// with its key. Returns `None` if they don't both have top-level entries.
+fn grouped_diff(left: &Value, right: &Value, config: &SortConfig) -> Option<String> {
+    let mut output = String::new();
+    for entry in diff::top_level_entries(left, right)? {
+        if entry.left == entry.right {
+            continue;
+        }
+        let side = |value: Option<&Value>| value.map_or_else(|| "<missing>".to_string(), render);
+        let (left_output, right_output) = (side(entry.left), side(entry.right));
+        let diff = if config.unified_diff {
+            diff::unified_diff(&left_output, &right_output)
+        } else {
+            Comparison::new(&Rendered(&left_output), &Rendered(&right_output)).to_string()
+        };
+        if !output.is_empty() {
+            output.push_str("\n\n");
+        }
+        write!(output, "{}:\n{}", entry.key, diff).expect("writing to a String can't fail");
+    }
+    Some(output)
+}
+
+/// Appends a `heading` followed by an indented line for each item, if there are any items.
+fn append_section<T: fmt::Display>(
+    sections: &mut String,
+    heading: &str,
+    items: impl IntoIterator<Item = T>,
+) {
+    let mut items = items.into_iter().peekable();
+    if items.peek().is_none() {
+        return;
+    }
+
+    write!(sections, "\n\n{}", heading).expect("writing to a String can't fail");
+    for item in items {
+        write!(sections, "\n  {}", item).expect("writing to a String can't fail");
+    }
+}
+
+/// Compares the sorted Debug output of `left` and `right` and writes the (colored) diff
+/// to `w` if they differ, instead of panicking like [`assert_eq_sorted`]. Returns whether
+/// they matched.
+///
+/// Panics if the Debug output can't be parsed, like [`SortedDebug`].
+///
+/// ```rust
+/// use pretty_assertions_sorted::write_sorted_diff;
+///
+/// let mut log = Vec::new();
+/// assert!(!write_sorted_diff(&mut log, &[1, 2], &[1, 3]).unwrap());
+/// assert!(!log.is_empty());
+/// ```
+pub fn write_sorted_diff<W: io::Write, L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    w: &mut W,
+    left: &L,
+    right: &R,
+) -> io::Result<bool> {
+    match sorted_comparison(left, right, &SortConfig::default()) {
+        Err(err) => {
+            writeln!(w, "{}", err.diff())?;
+            Ok(false)
+        }
+        Ok(_) => Ok(true),
+    }
+}
+
+/// Compares the sorted Debug output of `left` and `right` and returns an exit code like
+/// `diff` does: `0` if they match and `1` if they differ. This is meant for scripted golden
+/// comparisons (eg. from build scripts) that shouldn't panic, use [`write_sorted_diff`] to
+/// also report the differences.
+///
+/// Panics if the Debug output can't be parsed, like [`SortedDebug`].
+///
+/// ```rust
+/// use pretty_assertions_sorted::sorted_diff_status;
+///
+/// assert_eq!(sorted_diff_status(&[1, 2], &[1, 2]), 0);
+/// assert_eq!(sorted_diff_status(&[1, 2], &[1, 3]), 1);
+/// ```
+pub fn sorted_diff_status<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+) -> i32 {
+    match sorted_comparison(left, right, &SortConfig::default()) {
+        Err(_) => 1,
+        Ok(_) => 0,
+    }
+}
+
+/// Compares the sorted Debug output of `left` and `right` and returns a JSON document
+/// listing the paths that were added, removed or changed, with their Debug output before
+/// and after. Useful for CI dashboards and other tooling.
+///
+/// Panics if the Debug output can't be parsed, like [`SortedDebug`].
+///
+/// ```rust
+/// use pretty_assertions_sorted::sorted_diff_report;
+/// use std::collections::HashMap;
+///
+/// let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+/// let right: HashMap<_, _> = [("b", 3), ("c", 4)].into_iter().collect();
+/// assert_eq!(
+///     sorted_diff_report(&left, &right),
+///     r#"{"added":[{"path":"[\"c\"]","after":"4"}],"removed":[{"path":"[\"a\"]","before":"1"}],"changed":[{"path":"[\"b\"]","before":"2","after":"3"}]}"#,
+/// );
+/// ```
+pub fn sorted_diff_report<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+) -> String {
+    let config = SortConfig::default();
+    let differences = diff::differences(
+        &sorted_value_with_config(left, &config),
+        &sorted_value_with_config(right, &config),
+    );
+    diff::json_report(&differences)
+}
+
+/// Sorts already captured Debug output (eg. `{:?}` or `{:#?}` output read from a file or
+/// another process) the same way [`SortedDebug`] does.
+///
+/// Differences in whitespace and trailing commas are tolerated, so output captured from
+/// different toolchains or with different formatting flags normalizes to the same string.
+/// The result is identical to displaying the value with [`SortedDebug::new`].
+///
+/// ```rust
+/// use pretty_assertions_sorted::sort_debug_string;
+///
+/// assert_eq!(sort_debug_string("{2: true, 1: false}").unwrap(), "{\n    1: false,\n    2: true,\n}");
+/// ```
+pub fn sort_debug_string(debug_output: &str) -> Result<String, ParseError> {
+    Ok(sorted_output(parse(debug_output)?, &SortConfig::default()))
+}
+
+/// Parses the Debug output of `v` into a [`Value`] tree and sorts it the same way
+/// [`SortedDebug`] does.
+///
+/// This is useful for building expected trees programmatically, see
+/// [`assert_value_eq_sorted`].
+///
+/// Panics if the Debug output can't be parsed, like [`SortedDebug`].
+pub fn sorted_value<T: fmt::Debug + ?Sized>(v: &T) -> Value {
+    sorted_value_with_config(v, &SortConfig::default())
+}
+
+/// Like [`sorted_value`], but normalizes and sorts according to `config`.
+pub fn sorted_value_with_config<T: fmt::Debug + ?Sized>(v: &T, config: &SortConfig) -> Value {
+    match try_sorted_value_with_config(v, config) {
+        Ok(value) => value,
+        Err(err) => panic_on_sort_error(err),
+    }
+}
+
+/// Returns the longest common subsequence of the elements of two lists (or sets / tuples)
+/// after sorting them, ie. the elements they share.
+///
+/// Returns an empty `Vec` if either side isn't a list. Panics if the Debug output can't be
+/// parsed, like [`SortedDebug`].
+///
+/// ```rust
+/// use pretty_assertions_sorted::sorted_list_lcs;
+///
+/// let shared = sorted_list_lcs(&vec![3, 1, 2], &vec![4, 2, 3]);
+/// assert_eq!(format!("{:?}", shared), "[2, 3]");
+/// ```
+pub fn sorted_list_lcs<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized>(
+    left: &L,
+    right: &R,
+) -> Vec<Value> {
+    let config = SortConfig::new().sort_sequences(true);
+    let elements = |v: Value| match v {
+        Value::List(List { values })
+        | Value::Set(Set { values })
+        | Value::Tuple(Tuple { values, .. }) => Some(values),
+        _ => None,
+    };
+    match (
+        elements(sorted_value_with_config(left, &config)),
+        elements(sorted_value_with_config(right, &config)),
+    ) {
+        (Some(left), Some(right)) => diff::longest_common_subsequence(left, &right),
+        _ => Vec::new(),
+    }
+}
+
+/// Compares two parsed [`Value`] trees structurally, treating maps and sets as unordered
+/// and lists and tuples as ordered. Unlike the assertions, nothing is rendered to a string.
+///
+/// ```rust
+/// use pretty_assertions_sorted::{values_eq_unordered, KeyValue, Map, Term, Value};
+///
+/// let entry = |key: &str, value: &str| KeyValue {
+///     key: Value::Term(Term::String(key.to_string())),
+///     value: Value::Term(Term::UnquotedRawString(value.to_string())),
+/// };
+/// let a = Value::Map(Map { values: vec![entry("a", "1"), entry("b", "2")] });
+/// let b = Value::Map(Map { values: vec![entry("b", "2"), entry("a", "1")] });
+/// assert!(values_eq_unordered(&a, &b));
+/// ```
+pub fn values_eq_unordered(a: &Value, b: &Value) -> bool {
+    let config = SortConfig::new().sort_sequences(false);
+    let (mut a, mut b) = (normalize::clone_value(a), normalize::clone_value(b));
+    sort_maps(&mut a, &config);
+    sort_maps(&mut b, &config);
+    a == b
+}
+
+#[track_caller]
+fn panic_on_sort_error(err: SortError) -> ! {
+    match err {
+        SortError::ParseFailed(err) => {
+            ::core::panic!("Failed to parse Debug output for sorting (please use `assert_eq!` instead and/or file an issue for your use-case)!\nError: {}", err)
+        }
+        err => ::core::panic!("Failed to sort Debug output!\nError: {}", err),
+    }
+}
+
+/// Like [`sorted_value_with_config`], but returns an error instead of panicking when the
+/// Debug output can't be sorted.
+pub fn try_sorted_value_with_config<T: fmt::Debug + ?Sized>(
+    v: &T,
+    config: &SortConfig,
+) -> Result<Value, SortError> {
+    let mut value = try_normalized_value(v, config)?;
+    sort_maps(&mut value, config);
+    Ok(value)
+}
+
+/// How many times sorting can be re-entered from within Debug implementations (eg. a
+/// Debug implementation that uses [`SortedDebug`]) before giving up.
+const MAX_REENTRANT_DEPTH: usize = 16;
+
+thread_local! {
+    static REENTRANT_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Tracks that the Debug output of a value is being formatted for sorting.
+struct ReentrancyGuard;
+
+impl ReentrancyGuard {
+    fn enter() -> Result<Self, SortError> {
+        REENTRANT_DEPTH.with(|depth| {
+            if depth.get() >= MAX_REENTRANT_DEPTH {
+                return Err(SortError::RecursionLimitReached);
+            }
+            depth.set(depth.get() + 1);
+            Ok(ReentrancyGuard)
+        })
+    }
+}
+
+impl Drop for ReentrancyGuard {
+    fn drop(&mut self) {
+        REENTRANT_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Parses and normalizes the Debug output of `v`, without sorting it.
+fn try_normalized_value<T: fmt::Debug + ?Sized>(
+    v: &T,
+    config: &SortConfig,
+) -> Result<Value, SortError> {
+    let (mut value, _) = try_parsed_value(v, config)?;
+    normalize(&mut value, config);
+    Ok(value)
+}
+
+/// Parses the Debug output of `v`, without normalizing or sorting it. Also returns the
+/// length of the Debug output.
+fn try_parsed_value<T: fmt::Debug + ?Sized>(
+    v: &T,
+    config: &SortConfig,
+) -> Result<(Value, usize), SortError> {
+    let mut debug_output = {
+        let _guard = ReentrancyGuard::enter()?;
+        match config.max_input_bytes {
+            // Stop the Debug implementation as soon as it exceeds the limit, so a cyclic
+            // or runaway implementation can't generate unbounded output.
+            Some(max_input_bytes) => {
+                let mut writer = LimitedWriter {
+                    output: String::new(),
+                    max_len: max_input_bytes,
+                };
+                fmt::write(&mut writer, format_args!("{:?}", v))
+                    .map_err(|_| SortError::InputTooLarge)?;
+                writer.output
+            }
+            None => format!("{:?}", v),
+        }
+    };
+
+    if config.locale_floats {
+        debug_output = parse::normalize_decimal_commas(&debug_output);
+    }
+
+    let options = parse::ParseOptions {
+        max_depth: config.max_sort_depth,
+        warn_max_depth: config.warn_max_sort_depth,
+        verbatim_map_values: config.verbatim_values,
+    };
+    let value = parse::parse_with_options(&debug_output, options)
+        .map_err(|err| SortError::ParseFailed(err.to_string()))?;
+    if config
+        .max_depth
+        .is_some_and(|max_depth| value_depth(&value) > max_depth)
+    {
+        return Err(SortError::DepthExceeded);
+    }
+
+    Ok((value, debug_output.len()))
+}
+
+/// A [`fmt::Write`] that fails once more than `max_len` bytes are written to it.
+struct LimitedWriter {
+    output: String,
+    max_len: usize,
+}
+
+impl fmt::Write for LimitedWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.output.len() + s.len() > self.max_len {
+            return Err(fmt::Error);
+        }
+        self.output.push_str(s);
+        Ok(())
+    }
+}
+
+/// Returns how many levels of nested structs / collections `v` contains.
+fn value_depth(v: &Value) -> usize {
+    let children_depth = match v {
+        Value::Struct(s) => s
+            .values
+            .iter()
+            .map(
+                |ident_value_or_non_exhaustive| match ident_value_or_non_exhaustive {
+                    OrNonExhaustive::Value(ident_value) => value_depth(&ident_value.value),
+                    OrNonExhaustive::NonExhaustive => 0,
+                },
+            )
+            .max(),
+        Value::Map(map) => map
+            .values
+            .iter()
+            .map(|key_value| value_depth(&key_value.key).max(value_depth(&key_value.value)))
+            .max(),
+        Value::Set(s) => s.values.iter().map(value_depth).max(),
+        Value::List(l) => l.values.iter().map(value_depth).max(),
+        Value::Tuple(t) => t.values.iter().map(value_depth).max(),
+        Value::Term(_) => return 0,
+    };
+    1 + children_depth.unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::assert_eq;
+    use std::cmp::Ordering;
+    use std::collections::{HashMap, HashSet};
+
+    const TEST_RERUNS_FOR_DETERMINISM: u32 = 100;
+
+    fn sorted_debug<T: fmt::Debug>(v: T) -> String {
+        format!("{:#?}", SortedDebug::new(v))
+    }
+
+    #[test]
+    fn noop_sorts() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            assert_eq!(sorted_debug(2), "2");
+        }
+    }
+
+    #[test]
+    fn sorts_hashmap() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            // Note that we have to create the HashMaps each try
+            // in order to induce non-determinism in the debug output.
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(1, true);
+                map.insert(2, true);
+                map.insert(20, true);
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    1: true,
+                    2: true,
+                    20: true,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_hashmaps_with_non_exhaustive_object_values() {
+        #[allow(unused)]
+        struct Foo {
+            value: f32,
+        }
+
+        impl fmt::Debug for Foo {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("Foo")
+                    .field("value", &self.value)
+                    .finish_non_exhaustive()
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(1, Foo { value: 10.1 });
+                map.insert(32, Foo { value: 2.0 });
+                map.insert(2, Foo { value: -1.5 });
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    1: Foo {
+                        value: 10.1,
+                        ..
+                    },
+                    2: Foo {
+                        value: -1.5,
+                        ..
+                    },
+                    32: Foo {
+                        value: 2.0,
+                        ..
+                    },
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_list() {
+        #[allow(unused)]
+        #[derive(Debug)]
+        struct Foo {
+            value: f32,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = Vec::new();
+                map.insert(0, Foo { value: 10.1 });
+                map.insert(1, Foo { value: 2.0 });
+                map.insert(2, Foo { value: -1.5 });
+                map
+            };
+
+            let expected = indoc!(
+                "[
+                    Foo {
+                        value: -1.5,
+                    },
+                    Foo {
+                        value: 10.1,
+                    },
+                    Foo {
+                        value: 2.0,
+                    },
+                ]"
+            );
+            let sorted = format!(
+                "{:#?}",
+                SortedDebug::with_config(item, SortConfig::new().sort_sequences(true))
+            );
+            println!("{}", sorted);
+            // let comparison = Comparison::new(
+            //     expected,
+            //     sorted.as_str()
+            // ).to_string();
+            println!("{}", expected);
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[test]
+    fn test_list_assert_eq_sorted() {
+        #[allow(unused)]
+        #[derive(Debug, PartialEq)]
         struct Foo {
             value: f32,
         }
 
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = Vec::new();
-                map.insert(0, Foo { value: 10.1 });
-                map.insert(1, Foo { value: 2.0 });
-                map.insert(2, Foo { value: -1.5 });
-                map
-            };
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = Vec::new();
+                map.insert(0, Foo { value: 10.1 });
+                map.insert(1, Foo { value: 2.0 });
+                map.insert(2, Foo { value: -1.5 });
+                map
+            };
+
+            let expected = vec![
+                Foo {
+                    value: -1.5,
+                },
+                Foo {
+                    value: 10.1,
+                },
+                Foo {
+                    value: 2.0,
+                },
+            ];
+            assert_eq_sorted_with!(SortConfig::new().sort_sequences(true), item, expected);
+        }
+    }
+
+    #[test]
+    fn test_list_assert_eq_sorted_1() {
+        #[allow(unused)]
+        #[derive(Debug, Clone, PartialEq)]
+        struct Foo {
+            value: f32,
+        }
+
+        let item = {
+            let mut map = Vec::new();
+            map.insert(0, Foo { value: 10.1 });
+            map.insert(1, Foo { value: 2.0 });
+            map.insert(2, Foo { value: -1.5 });
+            map
+        };
+
+        let expected = vec![
+            Foo {
+                value: -1.5,
+            },
+            Foo {
+                value: 10.1,
+            },
+            Foo {
+                value: 2.0,
+            },
+        ];
+
+        assert_eq_sorted_with!(SortConfig::new().sort_sequences(true), item, expected);
+    }
+
+    #[test]
+    fn sorts_maps_nested_in_tuples() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = vec![
+                ("zed".to_string(), {
+                    let mut map = HashMap::new();
+                    map.insert(2, true);
+                    map.insert(1, false);
+                    map
+                }),
+                ("alpha".to_string(), {
+                    let mut map = HashMap::new();
+                    map.insert(20, true);
+                    map.insert(10, true);
+                    map
+                }),
+            ];
+
+            let expected = indoc!(
+                "[
+                    (
+                        \"zed\",
+                        {
+                            1: false,
+                            2: true,
+                        },
+                    ),
+                    (
+                        \"alpha\",
+                        {
+                            10: true,
+                            20: true,
+                        },
+                    ),
+                ]"
+            );
+            let config = SortConfig::new().sort_sequences(false);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_config(item, config)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_list_of_trait_objects() {
+        trait Shape: fmt::Debug {}
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Circle {
+            radius: u8,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Square(u8);
+
+        #[derive(Debug)]
+        struct Point;
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Group {
+            members: HashMap<&'static str, u8>,
+        }
+
+        impl Shape for Circle {}
+        impl Shape for Square {}
+        impl Shape for Point {}
+        impl Shape for Group {}
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: Vec<Box<dyn Shape>> = vec![
+                Box::new(Square(2)),
+                Box::new(Point),
+                Box::new(Group {
+                    members: [("b", 2), ("a", 1)].into_iter().collect(),
+                }),
+                Box::new(Circle { radius: 3 }),
+                Box::new(Square(1)),
+            ];
+
+            let expected = indoc!(
+                "[
+                    Circle {
+                        radius: 3,
+                    },
+                    Group {
+                        members: {
+                            \"a\": 1,
+                            \"b\": 2,
+                        },
+                    },
+                    Square(
+                        1,
+                    ),
+                    Square(
+                        2,
+                    ),
+                    Point,
+                ]"
+            );
+            let config = SortConfig::new().sort_sequences(true);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_config(&item, config)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_list_of_results() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: Vec<Result<HashMap<&str, i32>, String>> = vec![
+                Ok({
+                    let mut map = HashMap::new();
+                    map.insert("b", 2);
+                    map.insert("a", 1);
+                    map
+                }),
+                Err("failed".to_string()),
+                Ok({
+                    let mut map = HashMap::new();
+                    map.insert("c", 3);
+                    map.insert("a", 0);
+                    map
+                }),
+            ];
+
+            // `Err` sorts before `Ok` because variants are ordered by name.
+            let expected = indoc!(
+                "[
+                    Err(
+                        \"failed\",
+                    ),
+                    Ok(
+                        {
+                            \"a\": 0,
+                            \"c\": 3,
+                        },
+                    ),
+                    Ok(
+                        {
+                            \"a\": 1,
+                            \"b\": 2,
+                        },
+                    ),
+                ]"
+            );
+            let config = SortConfig::new().sort_sequences(true);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_config(item, config)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_tuples_element_wise() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashSet<(i32, String)> = [(10, "a"), (9, "b"), (-1, "c"), (9, "a")]
+                .into_iter()
+                .map(|(n, s)| (n, s.to_string()))
+                .collect();
+
+            let expected = indoc!(
+                "{
+                    (
+                        -1,
+                        \"c\",
+                    ),
+                    (
+                        9,
+                        \"a\",
+                    ),
+                    (
+                        9,
+                        \"b\",
+                    ),
+                    (
+                        10,
+                        \"a\",
+                    ),
+                }"
+            );
+            let config = SortConfig::new().sort_sequences(false).sort_tuples(true);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_config(&item, config)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_sets_but_not_lists_without_sort_sequences() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            tags: HashSet<&'static str>,
+            log: Vec<&'static str>,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = Foo {
+                tags: ["zed", "alpha", "beta"].into_iter().collect(),
+                log: vec!["zed", "alpha", "beta"],
+            };
+
+            let expected = indoc!(
+                "Foo {
+                    tags: {
+                        \"alpha\",
+                        \"beta\",
+                        \"zed\",
+                    },
+                    log: [
+                        \"zed\",
+                        \"alpha\",
+                        \"beta\",
+                    ],
+                }"
+            );
+            let config = SortConfig::new().sort_sequences(false);
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_config(item, config)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn schema_decides_which_fields_are_sorted() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Timeline {
+            events: Vec<&'static str>,
+            tags: Vec<&'static str>,
+        }
+
+        let timeline = Timeline {
+            events: vec!["started", "paused", "finished"],
+            tags: vec!["urgent", "backend", "ops"],
+        };
+        let config = SortConfig::new().schema(&[
+            ("events", FieldKind::Ordered),
+            ("tags", FieldKind::Unordered),
+        ]);
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_config(&timeline, config)),
+            indoc!(
+                r#"Timeline {
+                    events: [
+                        "started",
+                        "paused",
+                        "finished",
+                    ],
+                    tags: [
+                        "backend",
+                        "ops",
+                        "urgent",
+                    ],
+                }"#
+            )
+        );
+    }
+
+    #[test]
+    fn sorts_only_the_unordered_fields() {
+        /// A map that keeps its insertion order, like `IndexMap`.
+        struct OrderedMap(Vec<(&'static str, u32)>);
+
+        impl fmt::Debug for OrderedMap {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Request {
+            headers: HashMap<&'static str, &'static str>,
+            params: OrderedMap,
+        }
+
+        let request = |headers: [(&'static str, &'static str); 2], params| Request {
+            headers: headers.into(),
+            params: OrderedMap(params),
+        };
+        let config = || SortConfig::new().unordered_only(&["headers"]);
+
+        let left = request(
+            [("host", "example.com"), ("accept", "*/*")],
+            vec![("page", 2), ("limit", 10)],
+        );
+        let reordered_headers = request(
+            [("accept", "*/*"), ("host", "example.com")],
+            vec![("page", 2), ("limit", 10)],
+        );
+        assert_eq_sorted_with!(config(), left, reordered_headers);
+
+        let reordered_params = request(
+            [("host", "example.com"), ("accept", "*/*")],
+            vec![("limit", 10), ("page", 2)],
+        );
+        assert!(__sorted_comparison(&left, &reordered_params, &config()).is_some());
+        assert!(__sorted_comparison(&left, &reordered_params, &SortConfig::new()).is_none());
+    }
+
+    #[test]
+    fn keeps_blocklisted_fields_in_original_order() {
+        struct Stages(Vec<(&'static str, HashMap<&'static str, u8>)>);
+
+        impl fmt::Debug for Stages {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Pipeline {
+            stages: Stages,
+            labels: HashMap<&'static str, Stages>,
+        }
+
+        let stages = || {
+            Stages(vec![
+                ("test", [("b", 2), ("a", 1)].into()),
+                ("build", [("d", 4), ("c", 3)].into()),
+            ])
+        };
+        let pipeline = Pipeline {
+            stages: stages(),
+            labels: [("stages", stages()), ("other", stages())].into(),
+        };
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_blocklist(&pipeline, &["stages"])),
+            indoc!(
+                r#"Pipeline {
+                    stages: {
+                        "test": {
+                            "a": 1,
+                            "b": 2,
+                        },
+                        "build": {
+                            "c": 3,
+                            "d": 4,
+                        },
+                    },
+                    labels: {
+                        "other": {
+                            "build": {
+                                "c": 3,
+                                "d": 4,
+                            },
+                            "test": {
+                                "a": 1,
+                                "b": 2,
+                            },
+                        },
+                        "stages": {
+                            "test": {
+                                "a": 1,
+                                "b": 2,
+                            },
+                            "build": {
+                                "c": 3,
+                                "d": 4,
+                            },
+                        },
+                    },
+                }"#
+            )
+        );
+    }
+
+    #[test]
+    fn applies_nested_field_rules_independently() {
+        struct Steps(Vec<(&'static str, HashMap<&'static str, u8>)>);
+
+        impl fmt::Debug for Steps {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Job {
+            steps: Steps,
+            log: Vec<&'static str>,
+            tags: Vec<&'static str>,
+        }
+
+        let job = || Job {
+            steps: Steps(vec![
+                ("test", [("retries", 2), ("jobs", 4)].into()),
+                ("build", [("jobs", 8)].into()),
+            ]),
+            log: vec!["started", "finished"],
+            tags: vec!["slow", "ci"],
+        };
+        let jobs: HashMap<&str, Job> = [("nightly", job()), ("lint", job())].into();
+        let config = SortConfig::new()
+            .sort_sequences(true)
+            .schema(&[("log", FieldKind::Ordered)])
+            .blocklist(&["steps"]);
+        let job = indoc!(
+            r#"Job {
+                steps: {
+                    "test": {
+                        "jobs": 4,
+                        "retries": 2,
+                    },
+                    "build": {
+                        "jobs": 8,
+                    },
+                },
+                log: [
+                    "started",
+                    "finished",
+                ],
+                tags: [
+                    "ci",
+                    "slow",
+                ],
+            }"#
+        )
+        .replace('\n', "\n    ");
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_config(&jobs, config)),
+            format!("{{\n    \"lint\": {},\n    \"nightly\": {},\n}}", job, job)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Moved elements:\n  [0] -> [2]: \"a\"")]
+    fn reports_moved_list_elements() {
+        let config = SortConfig::new().sort_sequences(false).detect_moves(true);
+        assert_eq_sorted_with!(config, vec!["a", "b", "c"], vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn reports_field_differences_of_paired_list_elements() {
+        #[allow(dead_code)]
+        #[derive(Debug)]
+        struct Item {
+            name: &'static str,
+            count: u32,
+        }
+
+        let left = vec![
+            Item {
+                name: "a",
+                count: 1,
+            },
+            Item {
+                name: "b",
+                count: 2,
+            },
+        ];
+        let right = vec![
+            Item {
+                name: "a",
+                count: 3,
+            },
+            Item {
+                name: "c",
+                count: 2,
+            },
+        ];
+        let config = SortConfig::new()
+            .sort_sequences(false)
+            .pair_list_elements(true);
+        let err = __sorted_comparison(&left, &right, &config).unwrap();
+        assert!(err.diff().ends_with(
+            "\n\nDifferences between paired elements:\
+             \n  [0].count: 1 != 3\
+             \n  [1].name: \"b\" != \"c\""
+        ));
+    }
+
+    #[test]
+    fn groups_differences_by_top_level_key() {
+        let settings = |db_port, log_level| -> HashMap<&str, HashMap<&str, &str>> {
+            [
+                ("db", [("host", "localhost"), ("port", db_port)].into()),
+                ("cache", [("size", "64")].into()),
+                ("log", [("level", log_level)].into()),
+            ]
+            .into()
+        };
+        let config = SortConfig::new().group_by_top_key(true).unified_diff(true);
+        let err = __sorted_comparison(
+            &settings("5432", "info"),
+            &settings("5433", "debug"),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            err.diff(),
+            indoc!(
+                r#""db":
+                --- left
+                +++ right
+                @@ -1,4 +1,4 @@
+                 {
+                     "host": "localhost",
+                -    "port": "5432",
+                +    "port": "5433",
+                 }
+
+                "log":
+                --- left
+                +++ right
+                @@ -1,3 +1,3 @@
+                 {
+                -    "level": "info",
+                +    "level": "debug",
+                 }"#
+            )
+        );
+    }
+
+    #[test]
+    fn reports_type_name_mismatches_before_the_diff() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            id: u32,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Bar {
+            id: u32,
+        }
+
+        let err = __sorted_comparison(&Foo { id: 1 }, &Bar { id: 1 }, &SortConfig::new()).unwrap();
+        assert!(
+            err.diff().starts_with("type mismatch: Foo vs Bar\n\n"),
+            "{}",
+            err.diff()
+        );
+
+        let err = __sorted_comparison(
+            &vec![Some(Foo { id: 1 })],
+            &vec![Some(Bar { id: 1 })],
+            &SortConfig::new(),
+        )
+        .unwrap();
+        assert!(
+            err.diff()
+                .starts_with("type mismatch at [0][0]: Foo vs Bar\n\n"),
+            "{}",
+            err.diff()
+        );
+    }
+
+    #[test]
+    fn starts_with_a_summary_of_the_differences() {
+        let left: HashMap<&str, u8> = [("a", 1), ("b", 2), ("c", 3), ("d", 4)].into();
+        let right: HashMap<&str, u8> =
+            [("a", 1), ("b", 20), ("c", 30), ("e", 5), ("f", 6), ("g", 7)].into();
+        let err = __sorted_comparison(&left, &right, &SortConfig::new().summary(true)).unwrap();
+        assert!(
+            err.diff()
+                .starts_with("3 keys added, 1 removed, 2 changed\n\n"),
+            "{}",
+            err.diff()
+        );
+    }
+
+    #[test]
+    fn shows_differences_side_by_side() {
+        let left: HashMap<&str, u8> = [("b", 2), ("a", 1)].into();
+        let right: HashMap<&str, u8> = [("a", 1), ("b", 3)].into();
+        let config = SortConfig::new().side_by_side(true);
+        let err = __sorted_comparison(&left, &right, &config).unwrap();
+        assert_eq!(
+            err.diff(),
+            "{             {\n    \"a\": 1,       \"a\": 1,\n    \"b\": 2, |     \"b\": 3,\n}             }"
+        );
+    }
+
+    #[test]
+    fn sort_debug_string_normalizes_captured_output() {
+        let expected = indoc!(
+            "Foo {
+                map: {
+                    \"a\": 1,
+                    \"b\": 2,
+                },
+            }"
+        );
+        for input in [
+            "Foo { map: {\"b\": 2, \"a\": 1} }",
+            "Foo{map:{\"b\":2,\"a\":1}}",
+            "Foo {\n    map: {\n        \"b\": 2,\n        \"a\": 1,\n    },\n}",
+        ] {
+            assert_eq!(sort_debug_string(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn sort_debug_string_matches_sorted_debug() {
+        struct Empty;
+
+        impl fmt::Debug for Empty {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_struct("Empty").finish_non_exhaustive()
+            }
+        }
+
+        let value: HashMap<&str, (Vec<u8>, Empty)> =
+            [("b", (vec![2, 1], Empty)), ("a", (vec![], Empty))].into();
+        assert_eq!(
+            sort_debug_string(&format!("{:#?}", value)).unwrap(),
+            format!("{:?}", SortedDebug::new(&value))
+        );
+        assert!(sort_debug_string("Object {\"a\": Number(0)}").is_err());
+
+        // Cells are unwrapped like they are by `SortedDebug`.
+        let cell = std::cell::RefCell::new(HashMap::from([("b", 2), ("a", 1)]));
+        assert_eq!(
+            sort_debug_string(&format!("{:?}", cell)).unwrap(),
+            format!("{:?}", SortedDebug::new(&cell))
+        );
+        assert_eq!(
+            sort_debug_string(&format!("{:?}", cell)).unwrap(),
+            "{\n    \"a\": 1,\n    \"b\": 2,\n}"
+        );
+
+        // Struct fields keep their declared order, see `SortConfig::sort_struct_fields`.
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Point {
+            y: i32,
+            x: i32,
+        }
+
+        let point = Point { y: 1, x: 2 };
+        assert_eq!(
+            sort_debug_string(&format!("{:?}", point)).unwrap(),
+            format!("{:?}", SortedDebug::new(&point))
+        );
+        assert_eq!(
+            sort_debug_string(&format!("{:?}", point)).unwrap(),
+            "Point {\n    y: 1,\n    x: 2,\n}"
+        );
+    }
+
+    #[test]
+    fn sorts_maps_with_opaque_values() {
+        struct Opaque;
+
+        impl fmt::Debug for Opaque {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("<opaque>")
+            }
+        }
+
+        let tasks: HashMap<&str, Opaque> = [("upload", Opaque), ("download", Opaque)].into();
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&tasks)),
+            indoc!(
+                r#"{
+                    "download": <opaque>,
+                    "upload": <opaque>,
+                }"#
+            )
+        );
+    }
+
+    #[test]
+    fn sorts_structs_with_generic_type_names() {
+        struct Cache(HashMap<&'static str, u8>);
+
+        impl fmt::Debug for Cache {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_struct("Cache<HashMap<&str, u8>, RandomState>")
+                    .field("entries", &self.0)
+                    .finish()
+            }
+        }
+
+        let cache = Cache([("b", 2), ("a", 1)].into());
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&cache)),
+            indoc!(
+                r#"Cache<HashMap<&str, u8>, RandomState> {
+                    entries: {
+                        "a": 1,
+                        "b": 2,
+                    },
+                }"#
+            )
+        );
+    }
+
+    #[test]
+    fn lenient_wrapper_falls_back_to_unsorted_output() {
+        struct Unparseable;
+
+        impl fmt::Debug for Unparseable {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("Object {\"a\": Number(0)}")
+            }
+        }
+
+        assert_eq!(
+            format!("{:?}", SortedDebug::new_lenient(Unparseable)),
+            "Object {\"a\": Number(0)}"
+        );
+        let sorted = SortedDebug::new_lenient(HashMap::from([("b", 2), ("a", 1)]));
+        assert_eq!(
+            format!("{:?}", sorted),
+            "{\n    \"a\": 1,\n    \"b\": 2,\n}"
+        );
+    }
+
+    #[test]
+    fn fails_when_validation_rejects_a_side() {
+        let config = || {
+            SortConfig::new().validate(Box::new(|value| match value {
+                Value::Map(map) if map.values.len() > 2 => {
+                    Err(format!("too many entries: {}", map.values.len()))
+                }
+                _ => Ok(()),
+            }))
+        };
+        let small: HashMap<&str, u8> = [("b", 2), ("a", 1)].into();
+        assert_eq_sorted_with!(config(), small, small);
+
+        let large: HashMap<&str, u8> = [("c", 3), ("b", 2), ("a", 1)].into();
+        let err = __sorted_comparison(&large, &large, &config()).expect("validation fails");
+        assert_eq!(
+            err.diff(),
+            "validation of the left side failed: too many entries: 3"
+        );
+        let err = __sorted_comparison(&small, &large, &config()).expect("validation fails");
+        assert_eq!(
+            err.diff(),
+            "validation of the right side failed: too many entries: 3"
+        );
+    }
+
+    #[test]
+    fn sorts_values_with_special_floats() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Reading {
+            max: f64,
+            ratio: f64,
+            samples: HashMap<&'static str, f64>,
+        }
+
+        struct Histogram(Vec<(f64, u32)>);
+
+        impl fmt::Debug for Histogram {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        let reading = Reading {
+            max: f64::INFINITY,
+            ratio: f64::NAN,
+            samples: [("b", f64::NEG_INFINITY), ("a", f64::NAN)].into(),
+        };
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&reading)),
+            indoc!(
+                r#"Reading {
+                    max: inf,
+                    ratio: NaN,
+                    samples: {
+                        "a": NaN,
+                        "b": -inf,
+                    },
+                }"#
+            )
+        );
+
+        for entries in [
+            vec![
+                (f64::NAN, 1),
+                (1.5, 2),
+                (f64::INFINITY, 3),
+                (f64::NEG_INFINITY, 4),
+            ],
+            vec![
+                (f64::INFINITY, 3),
+                (f64::NEG_INFINITY, 4),
+                (1.5, 2),
+                (f64::NAN, 1),
+            ],
+        ] {
+            assert_eq!(
+                format!("{:?}", SortedDebug::new(Histogram(entries))),
+                "{\n    -inf: 4,\n    1.5: 2,\n    inf: 3,\n    NaN: 1,\n}"
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_values_nested_too_deeply_unsorted() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        enum Ast {
+            Leaf(HashSet<u8>),
+            Node(Box<Ast>),
+        }
+
+        let ast = |leaf| (0..5000).fold(Ast::Leaf(leaf), |ast, _| Ast::Node(Box::new(ast)));
+        assert_eq_sorted!(ast(HashSet::from([1])), ast(HashSet::from([1])));
+        assert!(__sorted_comparison(
+            &ast(HashSet::from([1])),
+            &ast(HashSet::from([2])),
+            &SortConfig::new()
+        )
+        .is_some());
+
+        let nested = vec![vec![HashSet::from([2, 1])]];
+        assert_eq!(
+            format!(
+                "{:?}",
+                SortedDebug::with_config(&nested, SortConfig::new().max_sort_depth(1))
+            ),
+            format!("[\n    {:?},\n]", nested[0])
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                SortedDebug::with_config(&nested, SortConfig::new().max_sort_depth(3))
+            ),
+            "[\n    [\n        {\n            1,\n            2,\n        },\n    ],\n]"
+        );
+    }
+
+    #[test]
+    fn orders_keys_and_elements_with_custom_comparator() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Priority(u32);
+
+        // Orders `Priority(n)` tuples by the number inside.
+        fn by_priority(a: &Value, b: &Value) -> Ordering {
+            match (a, b) {
+                (Value::Tuple(a), Value::Tuple(b)) => match (&a.values[..], &b.values[..]) {
+                    (
+                        [Value::Term(Term::UnquotedRawString(a))],
+                        [Value::Term(Term::UnquotedRawString(b))],
+                    ) => a.parse::<u32>().unwrap().cmp(&b.parse::<u32>().unwrap()),
+                    _ => Ordering::Equal,
+                },
+                _ => Ordering::Equal,
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let queues: HashMap<Priority, &str> = [
+                (Priority(10), "low"),
+                (Priority(3), "high"),
+                (Priority(5), "mid"),
+            ]
+            .into();
+            assert_eq!(
+                format!(
+                    "{:?}",
+                    SortedDebug::new(&queues).with_comparator(Box::new(by_priority))
+                ),
+                indoc!(
+                    r#"{
+                        Priority(
+                            3,
+                        ): "high",
+                        Priority(
+                            5,
+                        ): "mid",
+                        Priority(
+                            10,
+                        ): "low",
+                    }"#
+                )
+            );
+
+            let list = vec![Priority(10), Priority(3)];
+            let config = SortConfig::new()
+                .sort_sequences(true)
+                .comparator(Box::new(by_priority));
+            assert_eq_sorted_with!(config, list, vec![Priority(3), Priority(10)]);
+        }
+    }
+
+    #[test]
+    fn sorts_duplicate_keys_deterministically() {
+        struct MultiMap(Vec<(&'static str, u8)>);
+
+        impl fmt::Debug for MultiMap {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(k, v)| (k, v)))
+                    .finish()
+            }
+        }
+
+        let entries = [("b", 1), ("a", 2), ("a", 1), ("b", 2), ("a", 3)];
+        for rotation in 0..entries.len() {
+            let mut entries = entries.to_vec();
+            entries.rotate_left(rotation);
+            let multimap = MultiMap(entries);
+            assert_eq!(
+                format!("{:?}", SortedDebug::new(&multimap)),
+                indoc!(
+                    r#"{
+                        "a": 1,
+                        "a": 2,
+                        "a": 3,
+                        "b": 1,
+                        "b": 2,
+                    }"#
+                )
+            );
+            let config = SortConfig::new().sort_by_value(true);
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(&multimap, config)),
+                indoc!(
+                    r#"{
+                        "a": 1,
+                        "b": 1,
+                        "a": 2,
+                        "b": 2,
+                        "a": 3,
+                    }"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn compares_floats_within_tolerance() {
+        let left: HashMap<&str, f64> = [("x", 0.1 + 0.2), ("y", 1.5)].into();
+        let right: HashMap<&str, f64> = [("x", 0.3), ("y", 1.5)].into();
+        let config = || SortConfig::new().float_tolerance(1e-9);
+        assert_eq_sorted_with!(config(), left, right);
+        assert!(__sorted_comparison(&left, &right, &SortConfig::new()).is_some());
+
+        let right: HashMap<&str, f64> = [("x", 0.31), ("y", 1.5)].into();
+        assert!(__sorted_comparison(&left, &right, &config()).is_some());
+    }
+
+    #[test]
+    fn compares_floats_within_tolerance_in_either_order() {
+        let config = || SortConfig::new().float_tolerance(1e-9);
+        let left = vec![(0.1 + 0.2, "a"), (0.7, "b")];
+        let right = vec![(0.3, "a"), (0.7, "b")];
+        assert_eq_sorted_with!(config(), left, right);
+        assert_eq_sorted_with!(config(), right, left);
+
+        // Both sides show the shorter float, whichever side it's on.
+        let left = vec![(0.1 + 0.2, "a")];
+        let right = vec![(0.3, "b")];
+        for err in [
+            __sorted_comparison(&left, &right, &config()),
+            __sorted_comparison(&right, &left, &config()),
+        ] {
+            let err = err.expect("the strings differ");
+            assert_eq!(err.left().matches("0.3,").count(), 1);
+            assert_eq!(err.right().matches("0.3,").count(), 1);
+        }
+    }
+
+    #[test]
+    fn compares_str_and_string_keys_equally() {
+        let borrowed: HashMap<&str, u8> = [("b", 2), ("a", 1)].into();
+        let owned: HashMap<String, u8> = [("a".to_string(), 1), ("b".to_string(), 2)].into();
+        assert_eq_sorted!(borrowed, owned);
+    }
+
+    #[test]
+    fn sorts_maps_with_truncated_values() {
+        struct Truncated(&'static str);
+
+        impl fmt::Debug for Truncated {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{:?}...", self.0)
+            }
+        }
+
+        let previews: HashMap<&str, Truncated> = [
+            ("second", Truncated("Lorem")),
+            ("first", Truncated("Ipsum")),
+        ]
+        .into();
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&previews)),
+            indoc!(
+                r#"{
+                    "first": "Ipsum"...,
+                    "second": "Lorem"...,
+                }"#
+            )
+        );
+    }
+
+    #[test]
+    fn compares_signed_zeros_as_equal() {
+        let negative: HashMap<&str, f64> = [("x", -0.0), ("y", -1.5)].into();
+        let positive: HashMap<&str, f64> = [("x", 0.0), ("y", -1.5)].into();
+        let config = SortConfig::new().treat_signed_zero_equal(true);
+        assert_eq_sorted_with!(config, negative, positive);
+        assert!(__sorted_comparison(&negative, &positive, &SortConfig::new()).is_some());
+    }
+
+    #[test]
+    fn compares_pair_lists_as_maps() {
+        let left: Vec<(String, i32)> = vec![("b".to_string(), 2), ("a".to_string(), 1)];
+        let right: Vec<(String, i32)> = vec![("a".to_string(), 1), ("b".to_string(), 2)];
+        let config = || {
+            SortConfig::new()
+                .sort_sequences(false)
+                .treat_pair_lists_as_maps(true)
+        };
+        assert_eq_sorted_with!(config(), left, right);
+        assert!(
+            __sorted_comparison(&left, &right, &SortConfig::new().sort_sequences(false)).is_some()
+        );
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_config(&left, config())),
+            indoc!(
+                r#"{
+                    "a": 1,
+                    "b": 2,
+                }"#
+            )
+        );
+
+        // An empty list of any type isn't turned into a map.
+        let empty: Vec<u8> = vec![];
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_config(&empty, config())),
+            "[]"
+        );
+    }
+
+    #[test]
+    fn keeps_ansi_escapes_in_strings_atomic() {
+        let output: HashMap<&str, &str> = [
+            ("stdout", "\x1b[32mok\x1b[0m"),
+            ("stderr", "\x1b[31m{error}\x1b[0m"),
+        ]
+        .into();
+        // Debug escapes the control characters, so the diff's own coloring isn't affected.
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&output)),
+            indoc!(
+                r#"{
+                    "stderr": "\u{1b}[31m{error}\u{1b}[0m",
+                    "stdout": "\u{1b}[32mok\u{1b}[0m",
+                }"#
+            )
+        );
+
+        let mut changed = output.clone();
+        changed.insert("stdout", "\x1b[33mok\x1b[0m");
+        let err = __sorted_comparison(&output, &changed, &SortConfig::new()).unwrap();
+        assert!(!err.left().contains('\x1b'), "{}", err.left());
+        assert!(err.right().contains(r#""stdout": "\u{1b}[33mok\u{1b}[0m""#));
+    }
+
+    #[test]
+    fn sorts_duration_keys_chronologically() {
+        use std::time::Duration;
+
+        let timeouts: HashMap<Duration, &str> = [
+            (Duration::from_millis(1500), "slow"),
+            (Duration::from_millis(250), "fast"),
+            (Duration::from_micros(20), "instant"),
+            (Duration::from_secs(12), "glacial"),
+            (Duration::from_nanos(1_000_000_001), "second"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&timeouts)),
+            indoc!(
+                r#"{
+                    20µs: "instant",
+                    250ms: "fast",
+                    1.000000001s: "second",
+                    1.5s: "slow",
+                    12s: "glacial",
+                }"#
+            )
+        );
+    }
+
+    #[test]
+    fn sorts_enums_with_explicit_discriminants() {
+        #[derive(Clone, Copy)]
+        enum Priority {
+            Low = 1,
+            High = 3,
+        }
+
+        impl fmt::Debug for Priority {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let name = match self {
+                    Priority::Low => "Low",
+                    Priority::High => "High",
+                };
+                write!(f, "{} = {}", name, *self as i32)
+            }
+        }
+
+        let priorities: HashMap<&str, Priority> =
+            [("tests", Priority::Low), ("build", Priority::High)].into();
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&priorities)),
+            indoc!(
+                r#"{
+                    "build": High = 3,
+                    "tests": Low = 1,
+                }"#
+            )
+        );
+    }
+
+    #[test]
+    fn orders_string_keys_case_insensitively() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let config = || SortConfig::new().case_insensitive_keys(true);
+            let item: HashMap<&str, u32> =
+                [("Timeout", 30), ("retries", 3), ("MaxSize", 1024)].into();
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(&item, config())),
+                indoc!(
+                    r#"{
+                        "MaxSize": 1024,
+                        "retries": 3,
+                        "Timeout": 30,
+                    }"#
+                )
+            );
+
+            let names = vec!["bob", "Alice", "carol"];
+            assert_eq!(
+                format!(
+                    "{:?}",
+                    SortedDebug::with_config(&names, config().sort_sequences(true))
+                ),
+                "[\n    \"Alice\",\n    \"bob\",\n    \"carol\",\n]"
+            );
+
+            // Char keys aren't strings, they keep their case-sensitive order.
+            let grades: HashMap<char, u8> = [('a', 1), ('B', 2)].into();
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(&grades, config())),
+                "{\n    'B': 2,\n    'a': 1,\n}"
+            );
+        }
+    }
+
+    #[test]
+    fn orders_elements_that_only_differ_outside_the_config_deterministically() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            // These compare equal ignoring case, the tie is broken by their content.
+            let item: HashSet<&str> = ["key", "KEY", "Key"].into_iter().collect();
+            let config = SortConfig::new().case_insensitive_keys(true);
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(item, config)),
+                indoc!(
+                    r#"{
+                        "KEY",
+                        "Key",
+                        "key",
+                    }"#
+                )
+            );
+
+            // These keys are equal after being canonicalized, the tie is broken by their
+            // values.
+            let item: HashMap<&str, u8> = [("Key ", 2), ("key", 1), (" KEY", 3)].into();
+            let config = SortConfig::new().canonical_keys(true);
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(item, config)),
+                indoc!(
+                    r#"{
+                        "key": 1,
+                        "key": 2,
+                        "key": 3,
+                    }"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_variants_by_rank() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        enum Level {
+            Info,
+            Warn,
+            Error,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashSet<_> = [Level::Info, Level::Error, Level::Warn]
+                .into_iter()
+                .collect();
+
+            let config = SortConfig::new().variant_rank(Box::new(|variant| match variant {
+                "Error" => 0,
+                "Warn" => 1,
+                "Info" => 2,
+                _ => 3,
+            }));
+            let expected = indoc!(
+                "{
+                    Error,
+                    Warn,
+                    Info,
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_config(item, config)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn truncates_long_string_terms_in_output() {
+        let long_value = "x".repeat(100);
+        let config = SortConfig::new().max_term_len(4);
+        let item: HashMap<_, _> = [("blob", long_value.clone())].into_iter().collect();
+        let expected = indoc!(
+            "{
+                \"blob\": \"xxxx...[truncated 96 chars]\",
+            }"
+        );
+        assert_eq!(
+            format!("{:#?}", SortedDebug::with_config(&item, config)),
+            expected
+        );
+
+        // Equality is still decided on the full content.
+        let config = SortConfig::new().max_term_len(4);
+        let same: HashMap<_, _> = [("blob", long_value.clone())].into_iter().collect();
+        assert_eq!(__sorted_comparison(&item, &same, &config), None);
+        let different: HashMap<_, _> = [("blob", format!("{}y", long_value))].into_iter().collect();
+        let comparison = __sorted_comparison(&item, &different, &config)
+            .unwrap()
+            .to_string();
+        assert!(
+            comparison.contains("\"xxxx...[truncated 9"),
+            "{}",
+            comparison
+        );
+    }
+
+    #[test]
+    fn truncates_output_lines_for_display() {
+        let item: HashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        let config = SortConfig::new().max_output_lines(3).natural_order(true);
+
+        let expected = indoc!(
+            "{
+                0: 0,
+                1: 1,
+            ... (truncated)"
+        );
+        assert_eq!(
+            format!("{:#?}", SortedDebug::with_config(&item, config)),
+            expected
+        );
+
+        // Equality is still decided on the full content.
+        let config = SortConfig::new().max_output_lines(3);
+        let mut different = item.clone();
+        different.insert(99, 0);
+        assert_eq!(__sorted_comparison(&item, &item.clone(), &config), None);
+        let err = __sorted_comparison(&item, &different, &config).unwrap();
+        assert_eq!(err.left(), err.right());
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    #[allow(unused)]
+    enum State {
+        Active { since: u32 },
+        Paused { since: u32 },
+        Inactive,
+    }
+
+    #[test]
+    fn sorts_enum_variants_with_same_payload_shape() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashSet<_> = [
+                State::Paused { since: 1 },
+                State::Active { since: 1 },
+                State::Inactive,
+            ]
+            .into_iter()
+            .collect();
+
+            let expected = indoc!(
+                "{
+                    Active {
+                        since: 1,
+                    },
+                    Paused {
+                        since: 1,
+                    },
+                    Inactive,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_enum_variants_by_name_then_payload() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        enum Token {
+            B,
+            A(u8),
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashSet<_> = [Token::B, Token::A(2), Token::A(1)].into_iter().collect();
+            assert_eq!(
+                format!("{:?}", SortedDebug::new(item)),
+                indoc!(
+                    "{
+                        A(
+                            1,
+                        ),
+                        A(
+                            2,
+                        ),
+                        B,
+                    }"
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn groups_sorted_enum_lists_by_variant() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        enum Job {
+            Build(u8),
+            Archive(u8),
+        }
+
+        let jobs = vec![
+            Job::Build(2),
+            Job::Archive(3),
+            Job::Build(1),
+            Job::Archive(1),
+        ];
+        let config = SortConfig::new().sort_sequences(true);
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_config(&jobs, config)),
+            indoc!(
+                "[
+                    Archive(
+                        1,
+                    ),
+                    Archive(
+                        3,
+                    ),
+                    Build(
+                        1,
+                    ),
+                    Build(
+                        2,
+                    ),
+                ]"
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Active {")]
+    fn shows_enum_variant_changes_in_map_values() {
+        let left: HashMap<_, _> = [("a", State::Inactive), ("b", State::Active { since: 2 })]
+            .into_iter()
+            .collect();
+        let right: HashMap<_, _> = [("b", State::Inactive), ("a", State::Inactive)]
+            .into_iter()
+            .collect();
+        assert_eq_sorted!(left, right);
+    }
+
+    #[test]
+    fn compares_variants_only() {
+        let left: HashMap<_, _> = [("a", State::Active { since: 1 }), ("b", State::Inactive)]
+            .into_iter()
+            .collect();
+        let right: HashMap<_, _> = [("b", State::Inactive), ("a", State::Active { since: 2 })]
+            .into_iter()
+            .collect();
+        assert_eq_sorted_with!(SortConfig::new().variant_only(true), left, right);
+
+        let paused: HashMap<_, _> = [("a", State::Paused { since: 1 }), ("b", State::Inactive)]
+            .into_iter()
+            .collect();
+        let config = SortConfig::new().variant_only(true);
+        assert!(__sorted_comparison(&left, &paused, &config).is_some());
+    }
+
+    #[test]
+    fn exact_preset_matches_pretty_assertions() {
+        fn panic_message(f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+            let payload = std::panic::catch_unwind(f).unwrap_err();
+            payload.downcast_ref::<String>().unwrap().clone()
+        }
+
+        let left = vec![HashMap::from([("a", 1)]), HashMap::from([("b", 2)])];
+        let right = vec![HashMap::from([("b", 2)]), HashMap::from([("a", 1)])];
+        assert!(
+            __sorted_comparison(&left, &right, &SortConfig::new().sort_sequences(true)).is_none()
+        );
+        assert_eq!(
+            panic_message(|| assert_eq_sorted_with!(SortConfig::exact(), left, right, "{}", 1)),
+            panic_message(|| pretty_assertions::assert_eq!(left, right, "{}", 1))
+        );
+    }
+
+    #[test]
+    fn strict_preset_matches_default_behavior() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<_, _> = [("b", vec![3, 1]), ("A", vec![2])].into_iter().collect();
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_config(&item, SortConfig::strict())
+                ),
+                sorted_debug(&item)
+            );
+        }
+    }
+
+    #[test]
+    fn orders_alphanumeric_keys_and_elements_naturally() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let items: HashMap<&str, u8> = [("item2", 2), ("item10", 10), ("item1", 1)].into();
+            assert_eq!(
+                format!("{:?}", SortedDebug::new(&items)),
+                "{\n    \"item1\": 1,\n    \"item10\": 10,\n    \"item2\": 2,\n}"
+            );
+            let config = SortConfig::new().natural_order(true);
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(&items, config)),
+                "{\n    \"item1\": 1,\n    \"item2\": 2,\n    \"item10\": 10,\n}"
+            );
+
+            let numbers: HashSet<String> = ["10", "9", "100", "1"].map(String::from).into();
+            let config = SortConfig::new().natural_order(true);
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(&numbers, config)),
+                "{\n    \"1\",\n    \"9\",\n    \"10\",\n    \"100\",\n}"
+            );
+
+            let files = vec!["file10.txt", "file2.txt", "file1.txt"];
+            let config = SortConfig::new().natural_order(true).sort_sequences(true);
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(&files, config)),
+                "[\n    \"file1.txt\",\n    \"file2.txt\",\n    \"file10.txt\",\n]"
+            );
+        }
+    }
+
+    #[test]
+    fn orders_zero_padded_keys_numerically() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let agents: HashMap<&str, &str> = [
+                ("042", "answer"),
+                ("7", "lucky"),
+                ("0100", "century"),
+                ("007", "bond"),
+            ]
+            .into();
+            let config = SortConfig::new().natural_order(true);
+            assert_eq!(
+                format!("{:?}", SortedDebug::with_config(agents, config)),
+                indoc!(
+                    r#"{
+                        "007": "bond",
+                        "7": "lucky",
+                        "042": "answer",
+                        "0100": "century",
+                    }"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn json_like_preset_orders_keys_naturally_and_is_lenient() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<_, _> = [("item10", 1), ("Item2", 2), ("item1", 3)]
+                .into_iter()
+                .collect();
+
+            let expected = indoc!(
+                "{
+                    \"item1\": 3,
+                    \"Item2\": 2,
+                    \"item10\": 1,
+                }"
+            );
+            assert_eq!(
+                format!(
+                    "{:#?}",
+                    SortedDebug::with_config(item, SortConfig::json_like())
+                ),
+                expected
+            );
+        }
+
+        let unparseable = serde_json::json!({"a": 0});
+        assert_eq!(
+            format!(
+                "{:#?}",
+                SortedDebug::with_config(&unparseable, SortConfig::json_like())
+            ),
+            format!("{:#?}", unparseable)
+        );
+    }
+
+    #[test]
+    fn snapshot_preset_sorts_fields_and_trims_terms() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            zed: &'static str,
+            alpha: &'static str,
+        }
+
+        let item = Foo {
+            zed: "  padded ",
+            alpha: "a",
+        };
+        let expected = indoc!(
+            "Foo {
+                alpha: \"a\",
+                zed: \"padded\",
+            }"
+        );
+        assert_eq!(
+            format!(
+                "{:#?}",
+                SortedDebug::with_config(item, SortConfig::snapshot())
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn sorts_fields_of_enum_struct_variants() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        enum Event {
+            Login { user: &'static str, at: u32 },
+            Logout { user: &'static str, reason: Reason },
+        }
+
+        #[allow(unused)]
+        struct Reason {
+            code: u8,
+            details: &'static str,
+        }
+
+        impl fmt::Debug for Reason {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_struct("Reason")
+                    .field("details", &self.details)
+                    .field("code", &self.code)
+                    .finish_non_exhaustive()
+            }
+        }
+
+        let events = vec![
+            Event::Login {
+                user: "alice",
+                at: 7,
+            },
+            Event::Logout {
+                user: "alice",
+                reason: Reason {
+                    code: 2,
+                    details: "timeout",
+                },
+            },
+        ];
+        let config = SortConfig::new()
+            .sort_sequences(false)
+            .sort_struct_fields(true);
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_config(&events, config)),
+            indoc!(
+                r#"[
+                    Login {
+                        at: 7,
+                        user: "alice",
+                    },
+                    Logout {
+                        reason: Reason {
+                            code: 2,
+                            details: "timeout",
+                            ..
+                        },
+                        user: "alice",
+                    },
+                ]"#
+            )
+        );
+    }
+
+    #[test]
+    fn compares_structs_with_differently_ordered_fields() {
+        struct Generated {
+            id: u32,
+            name: &'static str,
+            reversed: bool,
+        }
+
+        impl fmt::Debug for Generated {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let mut s = f.debug_struct("Generated");
+                if self.reversed {
+                    s.field("name", &self.name).field("id", &self.id);
+                } else {
+                    s.field("id", &self.id).field("name", &self.name);
+                }
+                s.finish_non_exhaustive()
+            }
+        }
+
+        let declared = Generated {
+            id: 1,
+            name: "a",
+            reversed: false,
+        };
+        let reversed = Generated {
+            id: 1,
+            name: "a",
+            reversed: true,
+        };
+        assert_eq_sorted_with!(
+            SortConfig::new().sort_struct_fields(true),
+            declared,
+            reversed
+        );
+        assert!(__sorted_comparison(&declared, &reversed, &SortConfig::new()).is_some());
+    }
+
+    #[test]
+    fn orders_struct_fields_explicitly() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct User {
+            zip: u32,
+            created_at: u32,
+            age: u8,
+            name: &'static str,
+            id: u8,
+        }
+
+        let item = User {
+            zip: 12345,
+            created_at: 10,
+            age: 30,
+            name: "x",
+            id: 1,
+        };
+        let config = SortConfig::new().field_order(&["id", "name", "created_at"]);
+
+        let expected = indoc!(
+            "User {
+                id: 1,
+                name: \"x\",
+                created_at: 10,
+                age: 30,
+                zip: 12345,
+            }"
+        );
+        assert_eq!(
+            format!("{:#?}", SortedDebug::with_config(&item, config)),
+            expected
+        );
+    }
+
+    #[test]
+    fn sorts_hashmap_with_unit_values() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<_, ()> = [("b", ()), ("c", ()), ("a", ())].into_iter().collect();
+
+            let expected = indoc!(
+                "{
+                    \"a\": (),
+                    \"b\": (),
+                    \"c\": (),
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_object_with_hashmap() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            bar: Bar,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Bar {
+            count: HashMap<&'static str, Zed>,
+            value: usize,
+        }
+
+        #[derive(Debug)]
+        struct Zed;
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = Foo {
+                bar: Bar {
+                    count: {
+                        let mut map = HashMap::new();
+                        map.insert("hello world", Zed);
+                        map.insert("lorem ipsum", Zed);
+                        map
+                    },
+                    value: 200,
+                },
+            };
+
+            let expected = indoc!(
+                "Foo {
+                    bar: Bar {
+                        count: {
+                            \"hello world\": Zed,
+                            \"lorem ipsum\": Zed,
+                        },
+                        value: 200,
+                    },
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_object_values() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            value: f32,
+            bar: Vec<Bar>,
+        }
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Bar {
+            elo: i32,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(
+                    "foo",
+                    Foo {
+                        value: 12.2,
+                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
+                    },
+                );
+                map.insert(
+                    "foo2",
+                    Foo {
+                        value: -0.2,
+                        bar: vec![],
+                    },
+                );
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"foo\": Foo {
+                        value: 12.2,
+                        bar: [
+                            Bar {
+                                elo: 200,
+                            },
+                            Bar {
+                                elo: -12,
+                            },
+                        ],
+                    },
+                    \"foo2\": Foo {
+                        value: -0.2,
+                        bar: [],
+                    },
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_object_keys() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Foo {
+            value: i32,
+            bar: Vec<Bar>,
+        }
+
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Bar {
+            elo: i32,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(
+                    Foo {
+                        value: 12,
+                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
+                    },
+                    "foo",
+                );
+                map.insert(
+                    Foo {
+                        value: -2,
+                        bar: vec![],
+                    },
+                    "foo2",
+                );
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    Foo {
+                        value: -2,
+                        bar: [],
+                    }: \"foo2\",
+                    Foo {
+                        value: 12,
+                        bar: [
+                            Bar {
+                                elo: 200,
+                            },
+                            Bar {
+                                elo: -12,
+                            },
+                        ],
+                    }: \"foo\",
+                }"
+            );
+            let sorted = sorted_debug(item);
+            // println!("{}", sorted);
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_list_of_object_keys() {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Foo {
+            id: u8,
+            tags: HashMap<&'static str, bool>,
+        }
+
+        impl std::hash::Hash for Foo {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.id.hash(state);
+            }
+        }
+
+        fn foo(id: u8, tags: &[&'static str]) -> Foo {
+            Foo {
+                id,
+                tags: tags.iter().map(|tag| (*tag, true)).collect(),
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(vec![foo(1, &["y", "x"]), foo(2, &[])], "second");
+                map.insert(vec![foo(1, &["x", "w"])], "first");
+                map.insert(vec![foo(1, &["x", "y"]), foo(3, &[])], "third");
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    [
+                        Foo {
+                            id: 1,
+                            tags: {
+                                \"w\": true,
+                                \"x\": true,
+                            },
+                        },
+                    ]: \"first\",
+                    [
+                        Foo {
+                            id: 1,
+                            tags: {
+                                \"x\": true,
+                                \"y\": true,
+                            },
+                        },
+                        Foo {
+                            id: 2,
+                            tags: {},
+                        },
+                    ]: \"second\",
+                    [
+                        Foo {
+                            id: 1,
+                            tags: {
+                                \"x\": true,
+                                \"y\": true,
+                            },
+                        },
+                        Foo {
+                            id: 3,
+                            tags: {},
+                        },
+                    ]: \"third\",
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_maps_inside_transparent_wrappers() {
+        use std::mem::{ManuallyDrop, MaybeUninit};
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let map = || {
+                let mut map = HashMap::new();
+                map.insert("b", 2);
+                map.insert("a", 1);
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"a\": 1,
+                    \"b\": 2,
+                }"
+            );
+            assert_eq!(sorted_debug(Box::pin(map())), expected);
+
+            // The exact output of `ManuallyDrop` depends on the toolchain.
+            assert_eq!(
+                sorted_debug(ManuallyDrop::new(map())),
+                sorted_debug(ManuallyDrop::new(
+                    map().into_iter().collect::<HashMap<_, _>>()
+                ))
+            );
+
+            let item = {
+                let mut map = HashMap::new();
+                map.insert("b", MaybeUninit::<u8>::uninit());
+                map.insert("a", MaybeUninit::<u8>::uninit());
+                map
+            };
+            let expected = indoc!(
+                "{
+                    \"a\": MaybeUninit<u8>,
+                    \"b\": MaybeUninit<u8>,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+            sorted_debug(MaybeUninit::<HashMap<&str, i32>>::uninit());
+        }
+    }
+
+    #[test]
+    fn hashmap_with_unicode_identifiers() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Maß {
+            größe: u8,
+            einheit: Einheit,
+        }
+
+        #[derive(Debug)]
+        enum Einheit {
+            Fuß,
+            Millimeter,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(
+                    "b",
+                    Maß {
+                        größe: 2,
+                        einheit: Einheit::Fuß,
+                    },
+                );
+                map.insert(
+                    "a",
+                    Maß {
+                        größe: 1,
+                        einheit: Einheit::Millimeter,
+                    },
+                );
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"a\": Maß {
+                        größe: 1,
+                        einheit: Millimeter,
+                    },
+                    \"b\": Maß {
+                        größe: 2,
+                        einheit: Fuß,
+                    },
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_inclusive_range_values() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map: HashMap<&str, Box<dyn fmt::Debug>> = HashMap::new();
+                map.insert("d", Box::new(..));
+                map.insert("c", Box::new(..=9));
+                map.insert("b", Box::new(-5..=-1));
+                map.insert("a", Box::new(0..=9));
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"a\": 0..=9,
+                    \"b\": -5..=-1,
+                    \"c\": ..=9,
+                    \"d\": ..,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_with_scalar_keys() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<_, _> = [(true, "yes"), (false, "no")].into_iter().collect();
+            let expected = indoc!(
+                "{
+                    false: \"no\",
+                    true: \"yes\",
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+
+            let item: HashMap<u8, _> = [(7, "c"), (0, "a"), (3, "b")].into_iter().collect();
+            let expected = indoc!(
+                "{
+                    0: \"a\",
+                    3: \"b\",
+                    7: \"c\",
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_mixed_scalar_keys() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        enum Key {
+            Flag(bool),
+            Num(u8),
+            Unit,
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashSet<_> = [
+                Key::Num(2),
+                Key::Flag(true),
+                Key::Unit,
+                Key::Num(1),
+                Key::Flag(false),
+            ]
+            .into_iter()
+            .collect();
+            let expected = indoc!(
+                "{
+                    Flag(
+                        false,
+                    ),
+                    Flag(
+                        true,
+                    ),
+                    Num(
+                        1,
+                    ),
+                    Num(
+                        2,
+                    ),
+                    Unit,
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+
+        // Identifiers sort before strings, which sort before raw terms like numbers.
+        assert_eq!(
+            sort_debug_string("{1, \"a\", true, false}").unwrap(),
+            "{\n    false,\n    true,\n    \"a\",\n    1,\n}"
+        );
+    }
+
+    #[test]
+    fn hashmap_with_multi_line_string_values() {
+        let table = "{\n  b: 2,\n  a: 1,\n}\n| key | value: { .. } |";
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<_, _> = [("table", table), ("other", "[c, a]")]
+                .into_iter()
+                .collect();
+
+            let expected = format!(
+                "{{\n    \"other\": \"[c, a]\",\n    \"table\": {:?},\n}}",
+                table
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+
+        // Quote chars don't start a string either.
+        let item: HashMap<_, _> = [("a", '"'), ("b", '{')].into_iter().collect();
+        let expected = "{\n    \"a\": '\"',\n    \"b\": '{',\n}";
+        assert_eq!(sorted_debug(item), expected);
+    }
+
+    #[test]
+    fn hashmap_with_chrono_naivedate() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert(chrono::NaiveDate::from_ymd_opt(2000, 2, 14).unwrap(), "foo");
+                map.insert(chrono::NaiveDate::from_ymd_opt(2001, 4, 2).unwrap(), "foo");
+                map
+            };
+
+            dbg!(&item);
+
+            let expected = indoc!(
+                "{
+                    2000-02-14: \"foo\",
+                    2001-04-02: \"foo\",
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_hashmap_with_bitflags_values() {
+        /// Mimics the Debug output of a `bitflags`-generated type.
+        struct Flags(&'static [&'static str]);
+
+        impl fmt::Debug for Flags {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "Flags({})", self.0.join(" | "))
+            }
+        }
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item = {
+                let mut map = HashMap::new();
+                map.insert("b", Flags(&["READ", "WRITE"]));
+                map.insert("a", Flags(&["EXECUTE"]));
+                map
+            };
+
+            let expected = indoc!(
+                "{
+                    \"a\": Flags(
+                        EXECUTE,
+                    ),
+                    \"b\": Flags(
+                        READ | WRITE,
+                    ),
+                }"
+            );
+            assert_eq!(sorted_debug(item), expected);
+        }
+    }
+
+    #[test]
+    fn sorts_custom_debug_set_output() {
+        /// Emits its tags in insertion order through `debug_set`, like a custom set type.
+        struct Tags(Vec<Tag>);
+
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Tag {
+            name: &'static str,
+            weight: u8,
+        }
+
+        impl fmt::Debug for Tags {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_set().entries(&self.0).finish()
+            }
+        }
+
+        let item = {
+            let mut map = HashMap::new();
+            map.insert(
+                "b",
+                Tags(vec![
+                    Tag {
+                        name: "z",
+                        weight: 1,
+                    },
+                    Tag {
+                        name: "a",
+                        weight: 2,
+                    },
+                ]),
+            );
+            map.insert("a", Tags(vec![]));
+            map
+        };
+
+        let expected = indoc!(
+            "{
+                \"a\": {},
+                \"b\": {
+                    Tag {
+                        name: \"a\",
+                        weight: 2,
+                    },
+                    Tag {
+                        name: \"z\",
+                        weight: 1,
+                    },
+                },
+            }"
+        );
+        assert_eq!(sorted_debug(item), expected);
+    }
+
+    #[test]
+    fn keys_only_ignores_value_differences() {
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let left = {
+                let mut map = HashMap::new();
+                map.insert("a", vec![1, 2]);
+                map.insert("b", vec![]);
+                map
+            };
+            let right = {
+                let mut map = HashMap::new();
+                map.insert("b", vec![3]);
+                map.insert("a", vec![4, 5, 6]);
+                map
+            };
+
+            assert_eq_sorted_with!(SortConfig::new().keys_only(true), left, right);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "\"c\"")]
+    fn keys_only_reports_added_keys() {
+        let left: HashMap<_, _> = [("a", 1)].into_iter().collect();
+        let right: HashMap<_, _> = [("a", 1), ("c", 2)].into_iter().collect();
+        assert_eq_sorted_with!(SortConfig::new().keys_only(true), left, right);
+    }
+
+    #[test]
+    fn ignores_configured_list_indices() {
+        let config = SortConfig::new().ignore_list_indices(&[0]);
+        assert_eq_sorted_with!(
+            config,
+            vec!["header-1234", "a", "b"],
+            vec!["header-5678", "a", "b"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn ignored_list_indices_still_compare_other_elements() {
+        let config = SortConfig::new().ignore_list_indices(&[0]);
+        assert_eq_sorted_with!(config, vec!["header", "a"], vec!["header", "b"]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Differing elements at their original (unsorted) positions:\n  \
+                               left.items[2]: \"c\"\n  \
+                               right.items[0]: \"d\""
+    )]
+    fn reports_original_positions_of_differing_elements() {
+        #[derive(Debug)]
+        struct Foo {
+            #[allow(unused)]
+            items: Vec<&'static str>,
+        }
+
+        let config = SortConfig::new().report_original_positions(true);
+        assert_eq_sorted_with!(
+            config,
+            Foo {
+                items: vec!["b", "a", "c"],
+            },
+            Foo {
+                items: vec!["d", "a", "b"],
+            }
+        );
+    }
+
+    #[test]
+    fn panics_with_structured_payload() {
+        let left: HashMap<_, _> = [("b", 2), ("a", 1)].into_iter().collect();
+        let right: HashMap<_, _> = [("a", 1), ("b", 3)].into_iter().collect();
+
+        let payload = std::panic::catch_unwind(|| {
+            assert_eq_sorted_with!(
+                SortConfig::new().structured_panic(true),
+                left,
+                right,
+                "values of {}",
+                "b"
+            );
+        })
+        .unwrap_err();
+
+        let err = payload
+            .downcast_ref::<SortAssertionError>()
+            .expect("panics with a SortAssertionError");
+        assert_eq!(err.left(), "{\n    \"a\": 1,\n    \"b\": 2,\n}");
+        assert_eq!(err.right(), "{\n    \"a\": 1,\n    \"b\": 3,\n}");
+        assert_eq!(err.message(), Some("values of b"));
+        assert!(err
+            .to_string()
+            .starts_with("assertion failed: `(left == right)`: values of b\n\n"));
+    }
+
+    #[test]
+    fn reports_only_first_difference() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            a: HashMap<&'static str, Vec<u8>>,
+            b: &'static str,
+        }
+
+        let config = SortConfig::new().first_diff_only(true);
+        let left = Foo {
+            a: [("k", vec![1, 2]), ("l", vec![3])].into_iter().collect(),
+            b: "left",
+        };
+        let right = Foo {
+            a: [("k", vec![1, 5]), ("l", vec![3, 4])].into_iter().collect(),
+            b: "right",
+        };
+
+        let err = __sorted_comparison(&left, &right, &config).unwrap();
+        assert_eq!(
+            err.diff(),
+            "First difference at .a[\"k\"][1]:\n  left: 2\n  right: 5"
+        );
+        assert_eq!(__sorted_comparison(&left, &left, &config), None);
+    }
+
+    #[test]
+    fn compares_missing_keys_against_default() {
+        let config = || SortConfig::new().default_for_missing(Term::Ident("None".to_string()));
+        let full: HashMap<_, _> = [("a", Some(1)), ("b", None), ("c", None)]
+            .into_iter()
+            .collect();
+        let partial: HashMap<_, _> = [("a", Some(1))].into_iter().collect();
+        assert_eq_sorted_with!(config(), full, partial);
+        assert_eq_sorted_with!(config(), partial, full);
+
+        let different: HashMap<_, _> = [("a", Some(1)), ("b", Some(2))].into_iter().collect();
+        assert!(__sorted_comparison(&partial, &different, &config()).is_some());
+
+        // Maps nested in lists get their defaults too.
+        assert_eq_sorted_with!(config(), vec![full.clone()], vec![partial.clone()]);
+
+        // Inserted entries are ordered like the other entries.
+        let config = || {
+            SortConfig::new()
+                .default_for_missing(Term::UnquotedRawString("1".to_string()))
+                .sort_by_value(true)
+        };
+        let full: HashMap<_, _> = [("x", 3), ("y", 1)].into_iter().collect();
+        let partial: HashMap<_, _> = [("x", 3)].into_iter().collect();
+        assert_eq_sorted_with!(config(), full, partial);
+    }
+
+    #[test]
+    fn trims_whitespace_in_string_terms() {
+        let left: HashMap<_, _> = [("a", "value "), ("b", " other")].into_iter().collect();
+        let right: HashMap<_, _> = [("a", "value"), ("b", "other")].into_iter().collect();
+        assert_eq_sorted_with!(SortConfig::new().trim_terms(true), left, right);
+        assert!(__sorted_comparison(&left, &right, &SortConfig::new()).is_some());
+    }
+
+    #[test]
+    fn compares_only_filtered_entries() {
+        let config = || {
+            SortConfig::new().filter_entries(Box::new(|key, _value| {
+                matches!(key, Value::Term(Term::String(key)) if key.starts_with("user."))
+            }))
+        };
+        let left: HashMap<_, _> = [("user.name", "a"), ("user.id", "1"), ("session", "x")]
+            .into_iter()
+            .collect();
+        let right: HashMap<_, _> = [("user.id", "1"), ("user.name", "a"), ("session", "y")]
+            .into_iter()
+            .collect();
+        assert_eq_sorted_with!(config(), left, right);
+
+        let expected = indoc!(
+            "{
+                \"user.id\": \"1\",
+                \"user.name\": \"a\",
+            }"
+        );
+        assert_eq!(
+            format!("{:#?}", SortedDebug::with_config(&left, config())),
+            expected
+        );
+    }
+
+    #[test]
+    fn keeps_values_verbatim_while_sorting_keys() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Blob {
+            bytes: Vec<u8>,
+            labels: HashMap<&'static str, u8>,
+        }
+
+        let blob = |n| Blob {
+            bytes: vec![n, 2, 1],
+            labels: [("y", n), ("x", n)].into_iter().collect(),
+        };
+        let item: HashMap<_, _> = [("b", blob(2)), ("a", blob(1))].into_iter().collect();
+
+        let expected = format!(
+            "{{\n    \"a\": {:?},\n    \"b\": {:?},\n}}",
+            item["a"], item["b"]
+        );
+        let config = SortConfig::new().verbatim_values(true);
+        assert_eq!(
+            format!("{:#?}", SortedDebug::with_config(&item, config)),
+            expected
+        );
+
+        // Formatting the renderer wouldn't produce is kept byte-for-byte.
+        struct Spaced(u8);
+
+        impl fmt::Debug for Spaced {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "Spaced(  {} ,[ {} ] )", self.0, self.0)
+            }
+        }
+
+        let item: HashMap<_, _> = [("b", Spaced(2)), ("a", Spaced(1))].into_iter().collect();
+        let config = SortConfig::new().verbatim_values(true);
+        assert_eq!(
+            format!("{:?}", SortedDebug::with_config(&item, config)),
+            "{\n    \"a\": Spaced(  1 ,[ 1 ] ),\n    \"b\": Spaced(  2 ,[ 2 ] ),\n}"
+        );
+    }
+
+    #[test]
+    fn compares_hashmap_against_array_of_pairs() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo<T> {
+            counts: T,
+        }
+
+        let map: HashMap<_, _> = [(1, "one"), (2, "two"), (3, "three")].into_iter().collect();
+        assert_eq_sorted!(map, [(3, "three"), (1, "one"), (2, "two")]);
+        assert_eq_sorted!(
+            Foo {
+                counts: [(2, "two"), (1, "one"), (3, "three")]
+            },
+            Foo { counts: map }
+        );
+        assert!(__sorted_comparison(
+            &HashMap::<u8, u8>::new(),
+            &[(0u8, 0u8); 0],
+            &SortConfig::default()
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn calls_compare_hook() {
+        use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+        use std::sync::Mutex;
+
+        static CALLS: Mutex<Vec<(bool, usize)>> = Mutex::new(Vec::new());
+        set_on_compare(Box::new(|matched, input_bytes| {
+            CALLS.lock().unwrap().push((matched, input_bytes))
+        }));
+
+        // Other tests run concurrently, so look for sizes that are unique to this test.
+        let value = "calls_compare_hook".repeat(10);
+        assert_eq_sorted!(value, value);
+        let _ = __sorted_comparison(&value, "other", &SortConfig::new());
+
+        clear_on_compare();
+        {
+            let calls = CALLS.lock().unwrap();
+            assert!(calls.contains(&(true, 364)), "{:?}", calls);
+            assert!(calls.contains(&(false, 189)), "{:?}", calls);
+        }
+
+        // The hook can remove itself without deadlocking.
+        static SELF_REMOVING_CALLS: AtomicUsize = AtomicUsize::new(0);
+        set_on_compare(Box::new(|_, _| {
+            SELF_REMOVING_CALLS.fetch_add(1, AtomicOrdering::Relaxed);
+            clear_on_compare();
+        }));
+        assert_eq_sorted!(1, 1);
+        assert!(SELF_REMOVING_CALLS.load(AtomicOrdering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn finds_lcs_of_sorted_lists() {
+        let left = vec!["d", "a", "c", "b", "x"];
+        let right = vec!["b", "y", "c", "a", "e"];
+        let shared = sorted_list_lcs(&left, &right);
+        assert_eq!(format!("{:?}", shared), r#"["a", "b", "c"]"#);
+
+        assert!(sorted_list_lcs(&left, &5).is_empty());
+    }
+
+    #[test]
+    fn compares_value_trees_ignoring_map_order() {
+        let a = sorted_value(&vec![HashMap::from([("a", 1), ("b", 2)])]);
+        let reordered = parse(r#"[{"b": 2, "a": 1}]"#).unwrap();
+        assert!(values_eq_unordered(&a, &reordered));
+        assert!(values_eq_unordered(&reordered, &a));
+
+        let nested_set = parse(r#"Foo { tags: {"y", "x"}, log: ["a", "b"] }"#).unwrap();
+        let reordered_set = parse(r#"Foo { tags: {"x", "y"}, log: ["a", "b"] }"#).unwrap();
+        assert!(values_eq_unordered(&nested_set, &reordered_set));
+    }
+
+    #[test]
+    fn compares_different_value_trees_as_unequal() {
+        let a = parse(r#"{"a": 1, "b": 2}"#).unwrap();
+        for b in [
+            r#"{"a": 1, "b": 3}"#,
+            r#"{"a": 1}"#,
+            r#"{"a": 1, "c": 2}"#,
+            r#"[("a", 1), ("b", 2)]"#,
+        ] {
+            assert!(!values_eq_unordered(&a, &parse(b).unwrap()), "{}", b);
+        }
+
+        let list = parse("[1, 2]").unwrap();
+        let reversed = parse("[2, 1]").unwrap();
+        assert!(!values_eq_unordered(&list, &reversed));
+    }
+
+    #[test]
+    fn parses_floats_with_decimal_commas() {
+        /// Formats its value like a German locale would.
+        struct Messwert(f32);
+
+        impl fmt::Debug for Messwert {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "Messwert({})", self.0.to_string().replace('.', ","))
+            }
+        }
+
+        let item: HashMap<_, _> = [("b", Messwert(-0.25)), ("a", Messwert(1.5))]
+            .into_iter()
+            .collect();
+
+        let expected = indoc!(
+            "{
+                \"a\": Messwert(
+                    1.5,
+                ),
+                \"b\": Messwert(
+                    -0.25,
+                ),
+            }"
+        );
+        let config = SortConfig::new().locale_floats(true);
+        assert_eq!(
+            format!("{:#?}", SortedDebug::with_config(&item, config)),
+            expected
+        );
+    }
+
+    #[test]
+    fn degrades_gracefully_when_debug_sorts_itself() {
+        /// Sorts a child value in its Debug implementation, which is fine.
+        struct SortsChild(HashMap<&'static str, u8>);
+
+        impl fmt::Debug for SortsChild {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "SortsChild({:?})", SortedDebug::new(&self.0))
+            }
+        }
+
+        /// Sorts itself in its Debug implementation, which would recurse forever.
+        struct SortsItself;
+
+        impl fmt::Debug for SortsItself {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "SortsItself({:?})", SortedDebug::new(self))
+            }
+        }
+
+        let item = SortsChild([("b", 2), ("a", 1)].into_iter().collect());
+        let expected = indoc!(
+            "SortsChild(
+                {
+                    \"a\": 1,
+                    \"b\": 2,
+                },
+            )"
+        );
+        assert_eq!(sorted_debug(item), expected);
+
+        let output = sorted_debug(SortsItself);
+        assert!(output.contains("<recursive>"), "{}", output);
+    }
+
+    #[test]
+    fn returns_diff_status() {
+        let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        let right: HashMap<_, _> = [("b", 2), ("a", 1)].into_iter().collect();
+        assert_eq!(sorted_diff_status(&left, &right), 0);
+
+        let right: HashMap<_, _> = [("b", 3), ("a", 1)].into_iter().collect();
+        assert_eq!(sorted_diff_status(&left, &right), 1);
+    }
+
+    #[test]
+    fn reports_differences_as_json() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Config {
+            name: &'static str,
+            limits: HashMap<&'static str, u32>,
+        }
+
+        let left = Config {
+            name: "a \"quoted\" name",
+            limits: [("cpu", 2), ("memory", 512)].into(),
+        };
+        let right = Config {
+            name: "another \"quoted\" name",
+            limits: [("cpu", 4), ("disk", 10)].into(),
+        };
+        let report: serde_json::Value =
+            serde_json::from_str(&sorted_diff_report(&left, &right)).unwrap();
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "added": [{"path": ".limits[\"disk\"]", "after": "10"}],
+                "removed": [{"path": ".limits[\"memory\"]", "before": "512"}],
+                "changed": [
+                    {
+                        "path": ".name",
+                        "before": "\"a \\\"quoted\\\" name\"",
+                        "after": "\"another \\\"quoted\\\" name\"",
+                    },
+                    {"path": ".limits[\"cpu\"]", "before": "2", "after": "4"},
+                ],
+            })
+        );
+
+        let report: serde_json::Value =
+            serde_json::from_str(&sorted_diff_report(&left, &left)).unwrap();
+        assert_eq!(
+            report,
+            serde_json::json!({"added": [], "removed": [], "changed": []})
+        );
+    }
+
+    #[test]
+    fn writes_sorted_diff_to_writer() {
+        let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        let right: HashMap<_, _> = [("b", 2), ("a", 1)].into_iter().collect();
+        let mut buffer = Vec::new();
+        assert!(write_sorted_diff(&mut buffer, &left, &right).unwrap());
+        assert!(buffer.is_empty());
+
+        let right: HashMap<_, _> = [("b", 3), ("a", 1)].into_iter().collect();
+        assert!(!write_sorted_diff(&mut buffer, &left, &right).unwrap());
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains("Diff"), "{}", output);
+        assert!(output.contains("\"a\": 1,"), "{}", output);
+    }
+
+    #[test]
+    fn compares_byte_arrays_against_byte_strings() {
+        /// Formats its bytes like `bytes::Bytes` does.
+        struct Bytes(&'static [u8]);
+
+        impl fmt::Debug for Bytes {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "b\"{}\"", self.0.escape_ascii())
+            }
+        }
+
+        let config = || SortConfig::new().bytes_as_string(true);
+        let left: HashMap<_, _> = [("a", b"hi".to_vec()), ("b", b"\x00\"\n".to_vec())]
+            .into_iter()
+            .collect();
+        let right: HashMap<_, _> = [("b", Bytes(b"\x00\"\n")), ("a", Bytes(b"hi"))]
+            .into_iter()
+            .collect();
+        assert_eq_sorted_with!(config(), left, right);
+
+        let expected = indoc!(
+            r#"{
+                "a": b"hi",
+                "b": b"\x00\"\n",
+            }"#
+        );
+        assert_eq!(
+            format!("{:#?}", SortedDebug::with_config(&left, config())),
+            expected
+        );
+    }
+
+    #[test]
+    fn ignores_volatile_count_fields() {
+        /// A reference-counted handle whose Debug output includes its (volatile) counts.
+        struct Handle(std::rc::Rc<HashMap<&'static str, u8>>);
+
+        impl fmt::Debug for Handle {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("Handle")
+                    .field("strong_count", &std::rc::Rc::strong_count(&self.0))
+                    .field("value", &self.0)
+                    .finish()
+            }
+        }
+
+        let shared: std::rc::Rc<HashMap<_, _>> =
+            std::rc::Rc::new([("b", 2), ("a", 1)].into_iter().collect());
+        let left = Handle(shared.clone());
+        let _other_owner = shared.clone();
+        let right = Handle(std::rc::Rc::new([("a", 1), ("b", 2)].into_iter().collect()));
+
+        assert!(__sorted_comparison(&left, &right, &SortConfig::new()).is_some());
+        assert_eq_sorted_with!(
+            SortConfig::new().ignore_fields(&["strong_count"]),
+            left,
+            right
+        );
+    }
+
+    #[test]
+    fn unifies_integers_and_floats() {
+        let config = || SortConfig::new().unify_numbers(true);
+        let left: HashMap<_, _> = [("a", 42), ("b", -7)].into_iter().collect();
+        let right: HashMap<_, _> = [("a", 42.0), ("b", -7.0)].into_iter().collect();
+        assert_eq_sorted_with!(config(), left, right);
+
+        let left: HashMap<_, _> = [(1, "one"), (2, "two")].into_iter().collect();
+        let right = vec![(2.0, "two"), (1.0, "one")];
+        assert_eq_sorted_with!(config(), left, right);
+
+        let left: HashMap<_, _> = [("a", 42)].into_iter().collect();
+        let right: HashMap<_, _> = [("a", 42.5)].into_iter().collect();
+        assert!(__sorted_comparison(&left, &right, &config()).is_some());
+        assert!(__sorted_comparison(&[42], &[42.0], &SortConfig::new()).is_some());
+
+        // Signed zeros are only equal with `treat_signed_zero_equal`.
+        assert!(__sorted_comparison(&[-0.0], &[0.0], &config()).is_some());
+        assert_eq_sorted_with!(config().treat_signed_zero_equal(true), [-0.0], [0]);
+    }
+
+    #[test]
+    fn reports_unified_diff() {
+        let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        let right: HashMap<_, _> = [("b", 3), ("a", 1)].into_iter().collect();
+        let err =
+            __sorted_comparison(&left, &right, &SortConfig::new().unified_diff(true)).unwrap();
+
+        let expected = indoc!(
+            r#"--- left
+            +++ right
+            @@ -1,4 +1,4 @@
+             {
+                 "a": 1,
+            -    "b": 2,
+            +    "b": 3,
+             }"#
+        );
+        assert_eq!(err.diff(), expected);
+    }
 
-            let expected = vec![
-                Foo {
-                    value: -1.5,
-                },
-                Foo {
-                    value: 10.1,
-                },
-                Foo {
-                    value: 2.0,
-                },
-            ];
-            assert_eq_sorted!(item, expected);
+    #[test]
+    fn canonicalizes_keys() {
+        let config = || SortConfig::new().canonical_keys(true);
+        let left: HashMap<_, _> = [(" Name ", "a"), ("ID", "1")].into_iter().collect();
+        let right: HashMap<_, _> = [("name", "a"), ("id ", "1")].into_iter().collect();
+        assert_eq_sorted_with!(config(), left, right);
+
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<_, _> = [("B", 1), (" b", 2), ("a", 3)].into_iter().collect();
+            let expected = indoc!(
+                "{
+                    \"a\": 3,
+                    \"b\": 1,
+                    \"b\": 2,
+                }"
+            );
+            assert_eq!(
+                format!("{:#?}", SortedDebug::with_config(&item, config())),
+                expected
+            );
         }
     }
 
     #[test]
-    fn test_list_assert_eq_sorted_1() {
+    fn applies_migrations_to_old_debug_output() {
+        #[derive(Debug)]
         #[allow(unused)]
-        #[derive(Debug, Clone, PartialEq)]
-        struct Foo {
-            value: f32,
+        struct User {
+            name: &'static str,
+            roles: HashSet<&'static str>,
         }
 
+        /// The Debug output of an older version of `User`.
+        struct OldUser;
 
-        let item = {
-            let mut map = Vec::new();
-            map.insert(0, Foo { value: 10.1 });
-            map.insert(1, Foo { value: 2.0 });
-            map.insert(2, Foo { value: -1.5 });
-            map
+        impl fmt::Debug for OldUser {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(r#"UserV1 { legacy_id: 7, username: "x", roles: {"b", "a"} }"#)
+            }
+        }
+
+        let config = || {
+            SortConfig::new().migrations(vec![
+                Migration::rename_type("UserV1", "User"),
+                Migration::rename_field("User", "username", "name"),
+                Migration::remove_field("User", "legacy_id"),
+            ])
+        };
+        let user = User {
+            name: "x",
+            roles: ["a", "b"].into_iter().collect(),
         };
+        assert!(__sorted_comparison(&OldUser, &user, &SortConfig::new()).is_some());
+        assert_eq_sorted_with!(config(), OldUser, user);
+    }
 
-        let expected = vec![
-            Foo {
-                value: -1.5,
-            },
-            Foo {
-                value: 10.1,
-            },
-            Foo {
-                value: 2.0,
-            },
-        ];
-        
-        assert_eq_sorted!(item, expected);
-        
+    #[test]
+    fn unwraps_options() {
+        let config = || SortConfig::new().unwrap_options(true);
+        let map: HashMap<_, _> = [("b", Some(2)), ("a", Some(1))].into_iter().collect();
+        let expected: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        assert_eq_sorted_with!(config(), Some(map), expected);
+        assert_eq_sorted_with!(config(), Some(Some(5)), 5);
+
+        assert!(__sorted_comparison(&Some(5), &5, &SortConfig::new()).is_some());
+        assert!(__sorted_comparison(&None::<u8>, &5, &config()).is_some());
     }
 
     #[test]
-    fn sorts_object_with_hashmap() {
+    fn unwraps_transparent_wrappers() {
         #[derive(Debug)]
         #[allow(unused)]
-        struct Foo {
-            bar: Bar,
+        struct Settings {
+            values: HashMap<&'static str, u8>,
         }
 
         #[derive(Debug)]
         #[allow(unused)]
-        struct Bar {
-            count: HashMap<&'static str, Zed>,
-            value: usize,
+        struct Wrapper {
+            inner: Settings,
         }
 
         #[derive(Debug)]
-        struct Zed;
+        #[allow(unused)]
+        struct Id(u8);
 
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = Foo {
-                bar: Bar {
-                    count: {
-                        let mut map = HashMap::new();
-                        map.insert("hello world", Zed);
-                        map.insert("lorem ipsum", Zed);
-                        map
-                    },
-                    value: 200,
-                },
-            };
+        let settings = || Settings {
+            values: [("a", 1), ("b", 2)].into(),
+        };
+        let config = || SortConfig::new().transparent_wrappers(&["Wrapper", "Id"]);
+        assert_eq_sorted_with!(config(), Wrapper { inner: settings() }, settings());
+        assert_eq_sorted_with!(config(), vec![Id(3)], vec![3]);
 
-            let expected = indoc!(
-                "Foo {
-                    bar: Bar {
-                        count: {
-                            \"hello world\": Zed,
-                            \"lorem ipsum\": Zed,
-                        },
-                        value: 200,
-                    },
-                }"
-            );
-            assert_eq!(sorted_debug(item), expected);
-        }
+        assert!(__sorted_comparison(
+            &Wrapper { inner: settings() },
+            &settings(),
+            &SortConfig::new()
+        )
+        .is_some());
     }
 
     #[test]
-    fn hashmap_with_object_values() {
+    fn sorts_maps_inside_cells() {
+        use std::cell::RefCell;
+
         #[derive(Debug)]
         #[allow(unused)]
-        struct Foo {
-            value: f32,
-            bar: Vec<Bar>,
+        struct Cache {
+            entries: RefCell<HashMap<&'static str, Cell<u8>>>,
         }
 
+        let cache = Cache {
+            entries: RefCell::new([("b", Cell::new(2)), ("a", Cell::new(1))].into()),
+        };
+        assert_eq!(
+            format!("{:?}", SortedDebug::new(&cache)),
+            indoc!(
+                r#"Cache {
+                    entries: {
+                        "a": 1,
+                        "b": 2,
+                    },
+                }"#
+            )
+        );
+        assert_eq_sorted!(cache, {
+            #[derive(Debug)]
+            #[allow(unused)]
+            struct Cache {
+                entries: HashMap<&'static str, u8>,
+            }
+            Cache {
+                entries: [("a", 1), ("b", 2)].into(),
+            }
+        });
+
+        let config = SortConfig::new().unwrap_cells(false);
+        assert!(format!("{:?}", SortedDebug::with_config(&cache, config)).contains("RefCell {"));
+    }
+
+    #[test]
+    fn keeps_user_defined_cells_with_more_fields() {
         #[derive(Debug)]
         #[allow(unused)]
-        struct Bar {
-            elo: i32,
+        struct Cell {
+            row: u8,
+            col: u8,
+            value: u8,
         }
 
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(
-                    "foo",
-                    Foo {
-                        value: 12.2,
-                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
-                    },
-                );
-                map.insert(
-                    "foo2",
-                    Foo {
-                        value: -0.2,
-                        bar: vec![],
-                    },
-                );
-                map
-            };
+        let left = Cell {
+            row: 0,
+            col: 1,
+            value: 5,
+        };
+        let right = Cell {
+            row: 1,
+            col: 0,
+            value: 5,
+        };
+        assert!(__sorted_comparison(&left, &right, &SortConfig::default()).is_some());
+    }
 
-            let expected = indoc!(
-                "{
-                    \"foo\": Foo {
-                        value: 12.2,
-                        bar: [
-                            Bar {
-                                elo: -12,
-                            },
-                            Bar {
-                                elo: 200,
-                            },
-                        ],
-                    },
-                    \"foo2\": Foo {
-                        value: -0.2,
-                        bar: [],
-                    },
-                }"
-            );
-            assert_eq!(sorted_debug(item), expected);
-        }
+    #[test]
+    fn transforms_keys_before_comparing() {
+        let config = || {
+            SortConfig::new().key_transform(Box::new(|key| {
+                key.strip_prefix("col_").unwrap_or(key).to_string()
+            }))
+        };
+        let prefixed: HashMap<&str, u8> = [("col_id", 1), ("col_name", 2)].into();
+        let plain: HashMap<&str, u8> = [("name", 2), ("id", 1)].into();
+        assert_eq_sorted_with!(config(), prefixed, plain);
+        assert!(__sorted_comparison(&prefixed, &plain, &SortConfig::new()).is_some());
     }
 
     #[test]
-    fn hashmap_with_object_keys() {
-        #[derive(Debug, PartialEq, Eq, Hash)]
-        struct Foo {
-            value: i32,
-            bar: Vec<Bar>,
+    fn sorted_debug_from_fields_matches_real_struct() {
+        #[allow(dead_code)]
+        #[derive(Debug)]
+        struct Account {
+            owner: &'static str,
+            balances: HashMap<&'static str, u32>,
         }
 
-        #[derive(Debug, PartialEq, Eq, Hash)]
-        struct Bar {
-            elo: i32,
-        }
+        let real = Account {
+            owner: "alice",
+            balances: [("usd", 5), ("eur", 3)].into_iter().collect(),
+        };
+        let balances: HashMap<_, _> = [("eur", 3), ("usd", 5)].into_iter().collect();
+        let fields: [(&str, &dyn fmt::Debug); 2] = [("owner", &"alice"), ("balances", &balances)];
+        let expected = SortedDebug::from_fields("Account", &fields);
+        assert_eq!(
+            format!("{:?}", expected),
+            format!("{:?}", SortedDebug::new(&real))
+        );
+        assert_eq_sorted!(expected, SortedDebug::new(&real));
+    }
+
+    #[test]
+    fn asserts_value_tree_against_hashmap() {
+        let expected = Value::Map(Map {
+            values: vec![
+                KeyValue {
+                    key: Value::Term(Term::UnquotedRawString("1".to_string())),
+                    value: Value::Term(Term::Ident("true".to_string())),
+                },
+                KeyValue {
+                    key: Value::Term(Term::UnquotedRawString("2".to_string())),
+                    value: Value::Term(Term::Ident("false".to_string())),
+                },
+            ],
+        });
 
         for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
             let item = {
                 let mut map = HashMap::new();
-                map.insert(
-                    Foo {
-                        value: 12,
-                        bar: vec![Bar { elo: 200 }, Bar { elo: -12 }],
-                    },
-                    "foo",
-                );
-                map.insert(
-                    Foo {
-                        value: -2,
-                        bar: vec![],
-                    },
-                    "foo2",
-                );
+                map.insert(2, false);
+                map.insert(1, true);
                 map
             };
 
-            let expected = indoc!(
-                "{
-                    Foo {
-                        value: -2,
-                        bar: [],
-                    }: \"foo2\",
-                    Foo {
-                        value: 12,
-                        bar: [
-                            Bar {
-                                elo: -12,
-                            },
-                            Bar {
-                                elo: 200,
-                            },
-                        ],
-                    }: \"foo\",
-                }"
-            );
-            let sorted = sorted_debug(item);
-            // println!("{}", sorted);
-            assert_eq!(sorted, expected);
+            assert_value_eq_sorted!(expected, item);
         }
     }
 
     #[test]
-    fn hashmap_with_chrono_naivedate() {
-        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
-            let item = {
-                let mut map = HashMap::new();
-                map.insert(chrono::NaiveDate::from_ymd_opt(2000, 2, 14).unwrap(), "foo");
-                map.insert(chrono::NaiveDate::from_ymd_opt(2001, 4, 2).unwrap(), "foo");
-                map
-            };
+    #[should_panic(expected = "assertion failed: `(expected == actual)`: custom message\n\n")]
+    fn value_tree_mismatch_panics() {
+        let expected = Value::Term(Term::UnquotedRawString("1".to_string()));
+        assert_value_eq_sorted!(expected, 2, "custom {}", "message");
+    }
 
-            dbg!(&item);
+    #[test]
+    fn try_sorted_value_reports_parse_failures() {
+        let err = try_sorted_value_with_config(&serde_json::json!({"a": 0}), &SortConfig::new())
+            .unwrap_err();
+        assert!(matches!(err, SortError::ParseFailed(_)));
+        assert!(err
+            .to_string()
+            .starts_with("failed to parse Debug output: Failed to consume all of string!"));
+    }
 
-            let expected = indoc!(
-                "{
-                    2000-02-14: \"foo\",
-                    2001-04-02: \"foo\",
-                }"
-            );
-            assert_eq!(sorted_debug(item), expected);
+    #[test]
+    fn try_sorted_value_reports_input_too_large() {
+        let config = SortConfig::new().max_input_bytes(8);
+        assert!(try_sorted_value_with_config(&"short", &config).is_ok());
+        assert_eq!(
+            try_sorted_value_with_config(&"much too long", &config),
+            Err(SortError::InputTooLarge)
+        );
+    }
+
+    #[test]
+    fn stops_runaway_debug_output_at_max_input_bytes() {
+        // Simulates a cyclic structure whose Debug implementation never terminates on its
+        // own.
+        struct Cycle;
+
+        impl fmt::Debug for Cycle {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                loop {
+                    f.write_str("Cycle { next: ")?;
+                }
+            }
+        }
+
+        let config = SortConfig::new().max_input_bytes(1024);
+        let err = try_sorted_value_with_config(&Cycle, &config).unwrap_err();
+        assert_eq!(err, SortError::InputTooLarge);
+        assert!(err
+            .to_string()
+            .contains("possible cyclic Debug implementation"));
+    }
+
+    #[test]
+    fn try_sorted_value_reports_depth_exceeded() {
+        let config = SortConfig::new().max_depth(2);
+        assert!(try_sorted_value_with_config(&vec![vec![1]], &config).is_ok());
+        assert_eq!(
+            try_sorted_value_with_config(&vec![vec![vec![1]]], &config),
+            Err(SortError::DepthExceeded)
+        );
+    }
+
+    #[test]
+    fn sorts_hashmap_with_system_time_values() {
+        use std::time::{Duration, SystemTime};
+
+        let epoch = SystemTime::UNIX_EPOCH;
+        for _ in 0..TEST_RERUNS_FOR_DETERMINISM {
+            let item: HashMap<_, _> = [("b", epoch + Duration::from_secs(5)), ("a", epoch)]
+                .into_iter()
+                .collect();
+
+            let value = sorted_value(&item);
+            let Value::Map(map) = &value else {
+                panic!("expected a map, got: {:?}", value);
+            };
+            assert_eq!(format!("{:?}", map.values[0].key), "\"a\"");
+            assert!(matches!(&map.values[0].value, Value::Struct(s) if s.name == "SystemTime"));
         }
+
+        let config = SortConfig::new().redact_timestamps(true);
+        let now: HashMap<_, _> = [("created", SystemTime::now())].into_iter().collect();
+        let then: HashMap<_, _> = [("created", epoch)].into_iter().collect();
+        assert_eq_sorted_with!(config, now, then);
     }
 
     #[test]
@@ -0,0 +1,93 @@
+//! Migrations that bridge changes of Debug formats, see
+//! [`SortConfig::migrations`](crate::SortConfig::migrations).
+use darrentsung_debug_parser::{OrNonExhaustive, Value};
+
+/// A change applied to the parsed Debug output before it's compared, eg. to make the
+/// output of an older version of a dependency match the output of a newer one.
+///
+/// ```rust
+/// use pretty_assertions_sorted::{Migration, SortConfig};
+///
+/// let config = SortConfig::new().migrations(vec![
+///     Migration::rename_field("User", "username", "name"),
+///     Migration::remove_field("User", "legacy_id"),
+/// ]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Migration {
+    /// Renames the field `from` of structs named `struct_name` to `to`.
+    RenameField {
+        struct_name: String,
+        from: String,
+        to: String,
+    },
+    /// Removes the field `field` of structs named `struct_name`.
+    RemoveField { struct_name: String, field: String },
+    /// Renames structs and named tuples (eg. enum variants) from `from` to `to`.
+    RenameType { from: String, to: String },
+}
+
+impl Migration {
+    pub fn rename_field(struct_name: &str, from: &str, to: &str) -> Self {
+        Migration::RenameField {
+            struct_name: struct_name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    pub fn remove_field(struct_name: &str, field: &str) -> Self {
+        Migration::RemoveField {
+            struct_name: struct_name.to_string(),
+            field: field.to_string(),
+        }
+    }
+
+    pub fn rename_type(from: &str, to: &str) -> Self {
+        Migration::RenameType {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    /// Applies the migration to `v` itself, not to its children.
+    pub(crate) fn apply(&self, v: &mut Value) {
+        match (self, v) {
+            (
+                Migration::RenameField {
+                    struct_name,
+                    from,
+                    to,
+                },
+                Value::Struct(s),
+            ) if s.name == *struct_name => {
+                for ident_value_or_non_exhaustive in &mut s.values {
+                    if let OrNonExhaustive::Value(ident_value) = ident_value_or_non_exhaustive {
+                        if ident_value.ident == *from {
+                            ident_value.ident = to.clone();
+                        }
+                    }
+                }
+            }
+            (Migration::RemoveField { struct_name, field }, Value::Struct(s))
+                if s.name == *struct_name =>
+            {
+                s.values.retain(
+                    |ident_value_or_non_exhaustive| match ident_value_or_non_exhaustive {
+                        OrNonExhaustive::Value(ident_value) => ident_value.ident != *field,
+                        OrNonExhaustive::NonExhaustive => true,
+                    },
+                );
+            }
+            (Migration::RenameType { from, to }, Value::Struct(s)) if s.name == *from => {
+                s.name = to.clone();
+            }
+            (Migration::RenameType { from, to }, Value::Tuple(t))
+                if t.name.as_ref() == Some(from) =>
+            {
+                t.name = Some(to.clone());
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,598 @@
+//! Transformations applied to a parsed [`Value`] tree before it's sorted, as requested by a
+//! [`SortConfig`].
+use darrentsung_debug_parser::{
+    IdentValue, KeyValue, List, Map, OrNonExhaustive, Set, Struct, Term, Tuple, Value,
+};
+
+use std::cmp::Ordering;
+
+use crate::sort::{compare_entries, compare_values, floats_within_tolerance};
+use crate::SortConfig;
+
+/// Types that are redacted by [`SortConfig::redact_timestamps`].
+const TIMESTAMP_TYPES: &[&str] = &["SystemTime", "Instant"];
+
+/// Types that are replaced by their `value` field by [`SortConfig::unwrap_cells`].
+const CELL_TYPES: &[&str] = &["Cell", "RefCell"];
+
+pub(crate) fn normalize(v: &mut Value, config: &SortConfig) {
+    for migration in &config.migrations {
+        migration.apply(v);
+    }
+
+    if config.treat_pair_lists_as_maps {
+        if let Some(map) = map_from_pairs(v) {
+            *v = map;
+        }
+    }
+
+    if let (Some(filter_entries), Value::Map(map)) = (&config.filter_entries, &mut *v) {
+        map.values
+            .retain(|key_value| filter_entries(&key_value.key, &key_value.value));
+    }
+
+    while let Some(inner) = transparent_wrapper_inner(v, config) {
+        *v = inner;
+    }
+
+    if config.unwrap_options {
+        while let Value::Tuple(Tuple {
+            name: Some(name),
+            values,
+        }) = v
+        {
+            if name != "Some" || values.len() != 1 {
+                break;
+            }
+            let inner = values.pop().expect("has a single value");
+            *v = inner;
+        }
+    }
+
+    if let Some(key_transform) = &config.key_transform {
+        match v {
+            Value::Map(map) => {
+                for key_value in &mut map.values {
+                    if let Value::Term(Term::String(key) | Term::Ident(key)) = &mut key_value.key {
+                        *key = key_transform(key);
+                    }
+                }
+            }
+            Value::Struct(s) => {
+                for field in &mut s.values {
+                    if let OrNonExhaustive::Value(field) = field {
+                        field.ident = key_transform(&field.ident);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if config.canonical_keys {
+        if let Value::Map(map) = v {
+            for key_value in &mut map.values {
+                if let Value::Term(Term::String(key) | Term::Ident(key)) = &mut key_value.key {
+                    *key = key.trim().to_lowercase();
+                }
+            }
+        }
+    }
+
+    if config.keys_only {
+        if let Value::Map(map) = v {
+            let keys = std::mem::take(&mut map.values)
+                .into_iter()
+                .map(|key_value| key_value.key)
+                .collect();
+            *v = Value::Set(Set { values: keys });
+        }
+    }
+
+    if config.bytes_as_string {
+        if let Some(bytes) = bytes(v) {
+            *v = Value::Term(Term::UnquotedRawString(byte_string(&bytes)));
+        }
+    }
+
+    if config.variant_only {
+        match v {
+            Value::Struct(Struct { name, .. })
+            | Value::Tuple(Tuple {
+                name: Some(name), ..
+            }) => *v = Value::Term(Term::Ident(std::mem::take(name))),
+            _ => (),
+        }
+    }
+
+    if let Value::Struct(s) = v {
+        if !config.ignore_fields.is_empty() {
+            s.values.retain(
+                |ident_value_or_non_exhaustive| match ident_value_or_non_exhaustive {
+                    OrNonExhaustive::Value(ident_value) => {
+                        !config.ignore_fields.contains(&ident_value.ident)
+                    }
+                    OrNonExhaustive::NonExhaustive => true,
+                },
+            );
+        }
+    }
+
+    if config.redact_timestamps {
+        if let Value::Struct(s) = v {
+            if TIMESTAMP_TYPES.contains(&s.name.as_str()) {
+                *v = Value::Term(Term::UnquotedRawString("<redacted>".to_string()));
+            }
+        }
+    }
+
+    if let Value::List(l) = v {
+        for &idx in &config.ignore_list_indices {
+            if let Some(element) = l.values.get_mut(idx) {
+                *element = Value::Term(Term::UnquotedRawString("<ignored>".to_string()));
+            }
+        }
+    }
+
+    if config.treat_signed_zero_equal {
+        if let Value::Term(Term::UnquotedRawString(number)) = v {
+            if let Some(unsigned) = number.strip_prefix('-') {
+                if float(unsigned) == Some(0.0) {
+                    *number = unsigned.to_string();
+                }
+            }
+        }
+    }
+
+    if config.unify_numbers {
+        if let Value::Term(Term::UnquotedRawString(number)) = v {
+            if let Some(integer) = integral_float(number) {
+                *number = integer;
+            }
+        }
+    }
+
+    if config.trim_terms {
+        if let Value::Term(Term::String(s)) = v {
+            let trimmed = s.trim();
+            if trimmed.len() != s.len() {
+                *s = trimmed.to_string();
+            }
+        }
+    }
+
+    for_each_child_mut(v, |child_v| normalize(child_v, config));
+}
+
+/// Takes the only field out of `v` if it's one of the
+/// [`SortConfig::transparent_wrappers`], or a cell type whose only field is `value` and
+/// [`SortConfig::unwrap_cells`] is set.
+fn transparent_wrapper_inner(v: &mut Value, config: &SortConfig) -> Option<Value> {
+    let is_wrapper = |name: &String| config.transparent_wrappers.contains(name);
+    let inner = match v {
+        Value::Struct(s) if config.unwrap_cells && CELL_TYPES.contains(&s.name.as_str()) => {
+            // A user-defined `Cell { row, col, value }` isn't a std cell, only unwrap when
+            // `value` is the only field.
+            match &mut s.values[..] {
+                [OrNonExhaustive::Value(field)] if field.ident == "value" => &mut field.value,
+                _ => return None,
+            }
+        }
+        Value::Struct(s) if is_wrapper(&s.name) => match &mut s.values[..] {
+            [OrNonExhaustive::Value(field)] => &mut field.value,
+            _ => return None,
+        },
+        Value::Tuple(Tuple {
+            name: Some(name),
+            values,
+        }) if is_wrapper(name) => match &mut values[..] {
+            [value] => value,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    // Leave a placeholder behind, `v` is replaced by its field right after.
+    Some(std::mem::replace(
+        inner,
+        Value::Term(Term::UnquotedRawString(String::new())),
+    ))
+}
+
+/// Returns the integer form of a float without a fractional part, eg. `42` for `42.0`.
+/// The sign of zero is kept (`-0` for `-0.0`), that's up to
+/// [`SortConfig::treat_signed_zero_equal`].
+fn integral_float(number: &str) -> Option<String> {
+    let float = float(number)?;
+    // Beyond 2^53 floats can't represent every integer, so they're left as-is.
+    (float.fract() == 0.0 && float.abs() < 2f64.powi(53)).then(|| {
+        let sign = if float == 0.0 && float.is_sign_negative() {
+            "-"
+        } else {
+            ""
+        };
+        format!("{}{}", sign, float as i64)
+    })
+}
+
+/// Returns the bytes of a byte array or byte string literal.
+fn bytes(v: &Value) -> Option<Vec<u8>> {
+    match v {
+        Value::List(l) => l
+            .values
+            .iter()
+            .map(|element| match element {
+                Value::Term(Term::UnquotedRawString(number)) => number.parse().ok(),
+                _ => None,
+            })
+            .collect(),
+        Value::Term(Term::UnquotedRawString(literal)) => {
+            unescape_bytes(literal.strip_prefix("b\"")?.strip_suffix('"')?)
+        }
+        _ => None,
+    }
+}
+
+fn unescape_bytes(escaped: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next()? {
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            '0' => b'\0',
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16).ok()?
+            }
+            c @ ('\\' | '"' | '\'') => c as u8,
+            _ => return None,
+        });
+    }
+    Some(bytes)
+}
+
+/// Formats `bytes` as a byte string literal, eg. `b"hi\n"`.
+fn byte_string(bytes: &[u8]) -> String {
+    let mut literal = String::from("b\"");
+    for &byte in bytes {
+        match byte {
+            b'\n' => literal.push_str("\\n"),
+            b'\r' => literal.push_str("\\r"),
+            b'\t' => literal.push_str("\\t"),
+            b'\\' | b'"' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            0x20..=0x7e => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Inserts `default` into maps of `left` and `right` for keys that only the other side has,
+/// recursing into values at the same path of both (sorted) trees.
+pub(crate) fn fill_missing_keys(
+    left: &mut Value,
+    right: &mut Value,
+    default: &Value,
+    config: &SortConfig,
+) {
+    match (left, right) {
+        (Value::Struct(l), Value::Struct(r)) if l.name == r.name => {
+            for l_field in &mut l.values {
+                let OrNonExhaustive::Value(l_field) = l_field else {
+                    continue;
+                };
+                let r_field = r.values.iter_mut().find_map(|r_field| match r_field {
+                    OrNonExhaustive::Value(r_field) if r_field.ident == l_field.ident => {
+                        Some(r_field)
+                    }
+                    _ => None,
+                });
+                if let Some(r_field) = r_field {
+                    fill_missing_keys(&mut l_field.value, &mut r_field.value, default, config);
+                }
+            }
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            let l_missing = missing_keys(r, l, default);
+            let r_missing = missing_keys(l, r, default);
+            let changed = !l_missing.is_empty() || !r_missing.is_empty();
+            l.values.extend(l_missing);
+            r.values.extend(r_missing);
+
+            for l_entry in &mut l.values {
+                if let Some(r_entry) = r
+                    .values
+                    .iter_mut()
+                    .find(|r_entry| r_entry.key == l_entry.key)
+                {
+                    fill_missing_keys(&mut l_entry.value, &mut r_entry.value, default, config);
+                }
+            }
+
+            // The keys are already sorted, only the inserted entries need to be moved into
+            // place.
+            if changed {
+                for map in [l, r] {
+                    map.values.sort_by(|a, b| compare_entries(a, b, config));
+                }
+            }
+        }
+        (Value::List(List { values: l }), Value::List(List { values: r }))
+        | (Value::Set(Set { values: l }), Value::Set(Set { values: r }))
+            if l.len() == r.len() =>
+        {
+            for (l, r) in l.iter_mut().zip(r) {
+                fill_missing_keys(l, r, default, config);
+            }
+        }
+        (Value::Tuple(l), Value::Tuple(r))
+            if l.name == r.name && l.values.len() == r.values.len() =>
+        {
+            for (l, r) in l.values.iter_mut().zip(&mut r.values) {
+                fill_missing_keys(l, r, default, config);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Gives the floats at the same path of `left` and `right` the same text when
+/// [`compare_terms`](crate::sort) considers them equal because of
+/// [`SortConfig::float_tolerance`], so that both render the same. The shorter text is kept
+/// on both sides, eg. `0.3` over `0.30000000000000004`, whichever side it's on.
+pub(crate) fn apply_float_tolerance(left: &mut Value, right: &mut Value, config: &SortConfig) {
+    match (left, right) {
+        (Value::Struct(l), Value::Struct(r)) if l.name == r.name => {
+            for l_field in &mut l.values {
+                let OrNonExhaustive::Value(l_field) = l_field else {
+                    continue;
+                };
+                let r_field = r.values.iter_mut().find_map(|r_field| match r_field {
+                    OrNonExhaustive::Value(r_field) if r_field.ident == l_field.ident => {
+                        Some(r_field)
+                    }
+                    _ => None,
+                });
+                if let Some(r_field) = r_field {
+                    apply_float_tolerance(&mut l_field.value, &mut r_field.value, config);
+                }
+            }
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            for l_entry in &mut l.values {
+                if let Some(r_entry) = r.values.iter_mut().find(|r_entry| {
+                    compare_values(&r_entry.key, &l_entry.key, config) == Ordering::Equal
+                }) {
+                    apply_float_tolerance(&mut l_entry.value, &mut r_entry.value, config);
+                }
+            }
+        }
+        (Value::Set(l), Value::Set(r)) => {
+            for (l, r) in l.values.iter_mut().zip(&mut r.values) {
+                apply_float_tolerance(l, r, config);
+            }
+        }
+        (Value::List(l), Value::List(r)) => {
+            for (l, r) in l.values.iter_mut().zip(&mut r.values) {
+                apply_float_tolerance(l, r, config);
+            }
+        }
+        (Value::Tuple(l), Value::Tuple(r)) if l.name == r.name => {
+            for (l, r) in l.values.iter_mut().zip(&mut r.values) {
+                apply_float_tolerance(l, r, config);
+            }
+        }
+        (Value::Term(Term::UnquotedRawString(l)), Value::Term(Term::UnquotedRawString(r)))
+            if floats_within_tolerance(l, r, config) =>
+        {
+            let shorter = if (l.len(), &*l) <= (r.len(), &*r) {
+                l.clone()
+            } else {
+                r.clone()
+            };
+            l.clone_from(&shorter);
+            *r = shorter;
+        }
+        _ => (),
+    }
+}
+
+/// Parses a float term, eg. `0.1` or `1e-7`. Integers aren't considered floats.
+pub(crate) fn float(number: &str) -> Option<f64> {
+    if !number.contains(['.', 'e', 'E']) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Turns a list of `(key, value)` pairs into a map when the other side has a map at the same
+/// path, so that eg. a `HashMap` can be compared against an array of pairs. Both trees must
+/// still be unsorted, since sorting a pair's tuple could reorder its key and value.
+pub(crate) fn maps_from_pairs(left: &mut Value, right: &mut Value) {
+    match (&mut *left, &mut *right) {
+        (Value::Map(_), Value::List(_)) => {
+            if let Some(map) = map_from_pairs(right) {
+                *right = map;
+            }
+        }
+        (Value::List(_), Value::Map(_)) => {
+            if let Some(map) = map_from_pairs(left) {
+                *left = map;
+            }
+        }
+        (Value::Struct(l), Value::Struct(r)) if l.name == r.name => {
+            for l_field in &mut l.values {
+                let OrNonExhaustive::Value(l_field) = l_field else {
+                    continue;
+                };
+                let r_field = r.values.iter_mut().find_map(|r_field| match r_field {
+                    OrNonExhaustive::Value(r_field) if r_field.ident == l_field.ident => {
+                        Some(r_field)
+                    }
+                    _ => None,
+                });
+                if let Some(r_field) = r_field {
+                    maps_from_pairs(&mut l_field.value, &mut r_field.value);
+                }
+            }
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            for l_entry in &mut l.values {
+                if let Some(r_entry) = r
+                    .values
+                    .iter_mut()
+                    .find(|r_entry| r_entry.key == l_entry.key)
+                {
+                    maps_from_pairs(&mut l_entry.value, &mut r_entry.value);
+                }
+            }
+        }
+        (Value::List(l), Value::List(r)) if l.values.len() == r.values.len() => {
+            for (l, r) in l.values.iter_mut().zip(&mut r.values) {
+                maps_from_pairs(l, r);
+            }
+        }
+        (Value::Tuple(l), Value::Tuple(r))
+            if l.name == r.name && l.values.len() == r.values.len() =>
+        {
+            for (l, r) in l.values.iter_mut().zip(&mut r.values) {
+                maps_from_pairs(l, r);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Converts `v` into a map if it's a non-empty list of unnamed 2-tuples. An empty list
+/// could be a list of anything, so it's left as-is.
+fn map_from_pairs(v: &mut Value) -> Option<Value> {
+    let Value::List(l) = v else {
+        return None;
+    };
+    let is_pair = |element: &Value| matches!(element, Value::Tuple(Tuple { name: None, values }) if values.len() == 2);
+    if l.values.is_empty() || !l.values.iter().all(is_pair) {
+        return None;
+    }
+
+    let mut values = Vec::new();
+    for element in std::mem::take(&mut l.values) {
+        if let Value::Tuple(t) = element {
+            let mut pair = t.values.into_iter();
+            let (key, value) = (pair.next()?, pair.next()?);
+            values.push(KeyValue { key, value });
+        }
+    }
+    Some(Value::Map(Map { values }))
+}
+
+/// Returns entries with `default` as their value for the keys of `from` that `to` lacks.
+fn missing_keys(from: &Map, to: &Map, default: &Value) -> Vec<KeyValue> {
+    from.values
+        .iter()
+        .filter(|from_entry| {
+            !to.values
+                .iter()
+                .any(|to_entry| to_entry.key == from_entry.key)
+        })
+        .map(|from_entry| KeyValue {
+            key: clone_value(&from_entry.key),
+            value: clone_value(default),
+        })
+        .collect()
+}
+
+/// Truncates string terms longer than `max_term_len` characters, used to keep huge values
+/// from blowing up the displayed diff.
+pub(crate) fn truncate_terms(v: &mut Value, max_term_len: usize) {
+    if let Value::Term(Term::String(s)) = v {
+        let len = s.chars().count();
+        if len > max_term_len {
+            let prefix: String = s.chars().take(max_term_len).collect();
+            *s = format!("{}...[truncated {} chars]", prefix, len - max_term_len);
+        }
+    }
+
+    for_each_child_mut(v, |child_v| truncate_terms(child_v, max_term_len));
+}
+
+/// Calls `f` with each direct child of `v` (struct field values, map keys and values and
+/// collection elements).
+pub(crate) fn for_each_child_mut(v: &mut Value, mut f: impl FnMut(&mut Value)) {
+    match v {
+        Value::Struct(s) => {
+            for ident_value_or_non_exhaustive in &mut s.values {
+                match ident_value_or_non_exhaustive {
+                    OrNonExhaustive::Value(ident_value) => f(&mut ident_value.value),
+                    OrNonExhaustive::NonExhaustive => (),
+                }
+            }
+        }
+        Value::Map(map) => {
+            for key_value in &mut map.values {
+                f(&mut key_value.key);
+                f(&mut key_value.value);
+            }
+        }
+        Value::Set(s) => s.values.iter_mut().for_each(f),
+        Value::List(l) => l.values.iter_mut().for_each(f),
+        Value::Tuple(t) => t.values.iter_mut().for_each(f),
+        Value::Term(_) => (),
+    }
+}
+
+/// Deep copies `v`, since the parsed types don't implement `Clone`.
+pub(crate) fn clone_value(v: &Value) -> Value {
+    match v {
+        Value::Struct(s) => Value::Struct(Struct {
+            name: s.name.clone(),
+            values: s
+                .values
+                .iter()
+                .map(
+                    |ident_value_or_non_exhaustive| match ident_value_or_non_exhaustive {
+                        OrNonExhaustive::Value(ident_value) => OrNonExhaustive::Value(IdentValue {
+                            ident: ident_value.ident.clone(),
+                            value: clone_value(&ident_value.value),
+                        }),
+                        OrNonExhaustive::NonExhaustive => OrNonExhaustive::NonExhaustive,
+                    },
+                )
+                .collect(),
+        }),
+        Value::Map(map) => Value::Map(Map {
+            values: map
+                .values
+                .iter()
+                .map(|key_value| KeyValue {
+                    key: clone_value(&key_value.key),
+                    value: clone_value(&key_value.value),
+                })
+                .collect(),
+        }),
+        Value::Set(s) => Value::Set(Set {
+            values: s.values.iter().map(clone_value).collect(),
+        }),
+        Value::List(l) => Value::List(List {
+            values: l.values.iter().map(clone_value).collect(),
+        }),
+        Value::Tuple(t) => Value::Tuple(Tuple {
+            name: t.name.clone(),
+            values: t.values.iter().map(clone_value).collect(),
+        }),
+        Value::Term(term) => Value::Term(match term {
+            Term::Ident(s) => Term::Ident(s.clone()),
+            Term::String(s) => Term::String(s.clone()),
+            Term::UnquotedRawString(s) => Term::UnquotedRawString(s.clone()),
+        }),
+    }
+}
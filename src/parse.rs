@@ -0,0 +1,788 @@
+//! Parser for the output of `std::fmt::Debug`.
+//!
+//! This follows the grammar of [`darrentsung_debug_parser`] and produces the same [`Value`]
+//! tree, but lives in this crate so that Debug output from common third-party crates can be
+//! supported without waiting on an upstream release.
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use darrentsung_debug_parser::{
+    IdentValue, KeyValue, List, Map, OrNonExhaustive, Set, Struct, Term, Tuple, Value,
+};
+
+/// How deeply values are parsed by default, see [`ParseOptions::max_depth`].
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Options for [`parse_with_options`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ParseOptions {
+    /// Values nested deeper than this are kept verbatim as raw string terms. Parsing (and
+    /// everything that walks the tree afterwards) is recursive, so this avoids overflowing
+    /// the stack on deeply nested input.
+    pub(crate) max_depth: usize,
+    /// Print a warning to stderr, once per process, when a value is nested deeper than
+    /// `max_depth`.
+    pub(crate) warn_max_depth: bool,
+    /// Keep map values as raw string terms of the exact text they were parsed from.
+    pub(crate) verbatim_map_values: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            warn_max_depth: false,
+            verbatim_map_values: false,
+        }
+    }
+}
+
+thread_local! {
+    /// The nesting depth of the value being parsed and the options it's parsed with.
+    static STATE: Cell<(usize, ParseOptions)> = Cell::new((0, ParseOptions::default()));
+}
+
+/// Whether the warning about values nested too deeply was already printed.
+static WARNED_MAX_DEPTH: AtomicBool = AtomicBool::new(false);
+
+/// Characters that can't be part of an unquoted raw string term.
+const RAW_STRING_DELIMITERS: &str = ":,{}[]()";
+
+/// Error returned when Debug output can't be parsed into a [`Value`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl ParseError {
+    fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.message, f)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The result of a single parsing step, `None` signals that the parser didn't match and
+/// the caller should backtrack.
+type ParseResult<'a, T> = Option<(&'a str, T)>;
+
+/// Parses the entirety of `input` into a [`Value`].
+pub fn parse(input: &str) -> Result<Value, ParseError> {
+    parse_with_options(input, ParseOptions::default())
+}
+
+/// Parses the entirety of `input` into a [`Value`] according to `options`.
+pub(crate) fn parse_with_options(input: &str, options: ParseOptions) -> Result<Value, ParseError> {
+    let previous = STATE.replace((0, options));
+    let result = parse_value(input);
+    STATE.set(previous);
+
+    let (rest, value) = result
+        .ok_or_else(|| ParseError::new(format!("Failed to parse a value!\nInput:\n{:?}", input)))?;
+    if !rest.trim().is_empty() {
+        return Err(ParseError::new(format!(
+            "Failed to consume all of string!\nValue:\n{:?}\n\nRest:\n{:?}",
+            value, rest
+        )));
+    }
+
+    Ok(value)
+}
+
+/// Replaces commas between two digits (outside of string and char literals) with dots, so
+/// that floats formatted with a comma decimal separator parse as a single term.
+pub(crate) fn normalize_decimal_commas(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut prev = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                // Copy the literal as-is, including escaped quotes.
+                output.push(c);
+                while let Some(literal_c) = chars.next() {
+                    output.push(literal_c);
+                    match literal_c {
+                        '\\' => output.extend(chars.next()),
+                        _ if literal_c == c => break,
+                        _ => (),
+                    }
+                }
+            }
+            ',' if prev.is_some_and(|prev: char| prev.is_ascii_digit())
+                && chars.peek().is_some_and(char::is_ascii_digit) =>
+            {
+                output.push('.')
+            }
+            c => output.push(c),
+        }
+        prev = output.chars().next_back();
+    }
+    output
+}
+
+fn parse_value(input: &str) -> ParseResult<'_, Value> {
+    let input = consume_ws(input);
+    let (depth, options) = STATE.get();
+    let max_depth = options.max_depth;
+    if depth >= max_depth {
+        if let Some((rest, nested)) = parse_nested_verbatim(input) {
+            if options.warn_max_depth && !WARNED_MAX_DEPTH.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "warning: Debug output is nested more than {} levels deep, deeper values \
+                     are left unsorted (see `SortConfig::max_sort_depth`)",
+                    max_depth
+                );
+            }
+            return Some((
+                rest,
+                Value::Term(Term::UnquotedRawString(nested.to_string())),
+            ));
+        }
+    }
+
+    STATE.set((depth + 1, options));
+    let result = parse_nested_value(input);
+    STATE.set((depth, options));
+    result
+}
+
+fn parse_nested_value(input: &str) -> ParseResult<'_, Value> {
+    parse_struct(input)
+        .map(|(rest, v)| (rest, Value::Struct(v)))
+        // Try map before set (because set will match map).
+        .or_else(|| parse_map(input).map(|(rest, v)| (rest, Value::Map(v))))
+        .or_else(|| parse_set(input).map(|(rest, v)| (rest, Value::Set(v))))
+        .or_else(|| parse_list(input).map(|(rest, v)| (rest, Value::List(v))))
+        .or_else(|| parse_tuple(input).map(|(rest, v)| (rest, Value::Tuple(v))))
+        .or_else(|| parse_term(input).map(|(rest, v)| (rest, Value::Term(v))))
+}
+
+/// Parses a (possibly named) struct, map, set, list or tuple without looking at its
+/// contents, except to find its end.
+fn parse_nested_verbatim(input: &str) -> ParseResult<'_, &str> {
+    let after_name = parse_name(input).map_or(input, |(rest, _)| consume_ws(rest));
+    if !after_name.starts_with(['{', '[', '(']) {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut rest = after_name;
+    loop {
+        if let Some((literal_rest, _)) = parse_string(rest) {
+            rest = literal_rest;
+            continue;
+        }
+        if let Some((literal_rest, _)) = parse_char(rest) {
+            rest = literal_rest;
+            continue;
+        }
+        let c = rest.chars().next()?;
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '{' | '[' | '(' => depth += 1,
+            '}' | ']' | ')' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            return Some((rest, &input[..input.len() - rest.len()]));
+        }
+    }
+}
+
+fn parse_struct(input: &str) -> ParseResult<'_, Struct> {
+    let (input, name) = parse_name(consume_ws(input))?;
+    let (rest, values) = parse_comma_separated_wrapped(input, "{", "}", parse_struct_field)?;
+    Some((rest, Struct { name, values }))
+}
+
+fn parse_struct_field(input: &str) -> ParseResult<'_, OrNonExhaustive<IdentValue>> {
+    let input = consume_ws(input);
+    parse_ident_value(input)
+        .map(|(rest, v)| (rest, OrNonExhaustive::Value(v)))
+        .or_else(|| {
+            // `...` is a truncation marker rather than the non-exhaustive marker.
+            tag(input, "..")
+                .filter(|rest| !rest.starts_with('.'))
+                .map(|rest| (rest, OrNonExhaustive::NonExhaustive))
+        })
+}
+
+fn parse_ident_value(input: &str) -> ParseResult<'_, IdentValue> {
+    let (input, ident) = parse_ident(consume_ws(input))?;
+    let input = tag(consume_ws(input), ":")?;
+    let (rest, value) = parse_value(input)?;
+    Some((rest, IdentValue { ident, value }))
+}
+
+fn parse_map(input: &str) -> ParseResult<'_, Map> {
+    let (rest, values) = parse_comma_separated_wrapped(input, "{", "}", parse_key_value)?;
+    Some((rest, Map { values }))
+}
+
+fn parse_key_value(input: &str) -> ParseResult<'_, KeyValue> {
+    let (input, key) = parse_value(input)?;
+    let input = tag(consume_ws(input), ":")?;
+    let (rest, value) = if STATE.get().1.verbatim_map_values {
+        parse_verbatim_value(input)?
+    } else {
+        parse_value(input)?
+    };
+    Some((consume_ws(rest), KeyValue { key, value }))
+}
+
+/// Parses a value, keeping the text it was parsed from as a raw string term. Nested values
+/// are only scanned for their end instead of being parsed.
+fn parse_verbatim_value(input: &str) -> ParseResult<'_, Value> {
+    let input = consume_ws(input);
+    let rest = match parse_nested_verbatim(input) {
+        Some((rest, _)) => rest,
+        None => parse_value(input)?.0,
+    };
+    let verbatim = input[..input.len() - rest.len()].trim_end();
+    Some((
+        rest,
+        Value::Term(Term::UnquotedRawString(verbatim.to_string())),
+    ))
+}
+
+fn parse_set(input: &str) -> ParseResult<'_, Set> {
+    let (rest, values) = parse_comma_separated_wrapped(input, "{", "}", parse_value)?;
+    Some((rest, Set { values }))
+}
+
+fn parse_list(input: &str) -> ParseResult<'_, List> {
+    let (rest, values) = parse_comma_separated_wrapped(input, "[", "]", parse_value)?;
+    Some((rest, List { values }))
+}
+
+fn parse_tuple(input: &str) -> ParseResult<'_, Tuple> {
+    let input = consume_ws(input);
+    let (input, name) = match parse_name(input) {
+        Some((rest, name)) => (rest, Some(name)),
+        None => (input, None),
+    };
+    let (rest, values) = parse_comma_separated_wrapped(input, "(", ")", parse_value)?;
+    // The unit type `()` is parsed as a term, an empty unnamed tuple can't be displayed.
+    if name.is_none() && values.is_empty() {
+        return None;
+    }
+    Some((rest, Tuple { name, values }))
+}
+
+/// Parses a term, joining terms separated by `|` into a single raw string term so that
+/// flag expressions (eg. `A | B` from `bitflags`) stay atomic. Likewise a term truncated
+/// with `...`, eg. `"abc"...`, is kept as a single raw string term.
+fn parse_term(input: &str) -> ParseResult<'_, Term> {
+    let input = consume_ws(input);
+    let (mut rest, mut term) = parse_single_term(input)?;
+    if let Some(truncated_rest) = tag(rest, "...") {
+        let consumed = input.len() - truncated_rest.len();
+        term = Term::UnquotedRawString(input[..consumed].to_string());
+        rest = truncated_rest;
+    }
+
+    while let Some((next_rest, _)) = tag(consume_ws(rest), "|").and_then(parse_single_term) {
+        let consumed = input.len() - next_rest.len();
+        term = Term::UnquotedRawString(input[..consumed].to_string());
+        rest = next_rest;
+    }
+
+    Some((rest, term))
+}
+
+fn parse_single_term(input: &str) -> ParseResult<'_, Term> {
+    let input = consume_ws(input);
+    if let Some(rest) = tag(input, "()") {
+        return Some((rest, Term::UnquotedRawString("()".to_string())));
+    }
+    if let Some((rest, char_literal)) = parse_char(input) {
+        return Some((rest, Term::UnquotedRawString(char_literal.to_string())));
+    }
+    if let Some((rest, byte_string)) = parse_byte_string(input) {
+        return Some((rest, Term::UnquotedRawString(byte_string.to_string())));
+    }
+    if let Some((rest, opaque)) = parse_angle_brackets(input) {
+        return Some((rest, Term::UnquotedRawString(opaque.to_string())));
+    }
+    if let Some((rest, type_name)) = parse_type_name(input) {
+        return Some((rest, Term::UnquotedRawString(type_name.to_string())));
+    }
+    if let Some((rest, ident)) = parse_ident(input) {
+        // An explicit discriminant, eg. `Variant = 3`, stays part of the identifier.
+        if let Some((discriminant_rest, Term::UnquotedRawString(_))) =
+            tag(consume_ws(rest), "=").and_then(parse_single_term)
+        {
+            let consumed = input.len() - discriminant_rest.len();
+            return Some((
+                discriminant_rest,
+                Term::Ident(input[..consumed].to_string()),
+            ));
+        }
+        // The special floats `inf` and `NaN` are numbers, like `-inf`.
+        if ident == "inf" || ident == "NaN" {
+            return Some((rest, Term::UnquotedRawString(ident)));
+        }
+        return Some((rest, Term::Ident(ident)));
+    }
+    if let Some((rest, string)) = parse_string(input) {
+        return Some((rest, Term::String(string)));
+    }
+
+    let len = input
+        .find(|c: char| c.is_whitespace() || RAW_STRING_DELIMITERS.contains(c))
+        .unwrap_or(input.len());
+    if len == 0 {
+        return None;
+    }
+    Some((
+        &input[len..],
+        Term::UnquotedRawString(input[..len].to_string()),
+    ))
+}
+
+fn parse_ident(input: &str) -> ParseResult<'_, String> {
+    let mut chars = input.char_indices();
+    match chars.next() {
+        // Rust identifiers can contain non-ASCII letters, eg. `größe`.
+        Some((_, c)) if c.is_alphabetic() || c == '_' => (),
+        _ => return None,
+    }
+
+    let len = chars
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+        .map_or(input.len(), |(idx, _)| idx);
+    Some((&input[len..], input[..len].to_string()))
+}
+
+/// Parses the name of a struct or tuple, which may be a full type name like
+/// `Wrapper<Vec<u8>, u32>` as well as a plain identifier.
+fn parse_name(input: &str) -> ParseResult<'_, String> {
+    parse_type_name(input)
+        .map(|(rest, name)| (rest, name.to_string()))
+        .or_else(|| parse_ident(input))
+}
+
+/// Parses a type name with a path and/or generic arguments, eg.
+/// `MaybeUninit<std::collections::hash::map::HashMap<&str, i32>>` as output by wrappers that
+/// print their type name instead of their contents. Plain identifiers aren't matched.
+fn parse_type_name(input: &str) -> ParseResult<'_, &str> {
+    let (mut rest, _) = parse_ident(input)?;
+    while let Some((next_rest, _)) = tag(rest, "::").and_then(parse_ident) {
+        rest = next_rest;
+    }
+
+    if rest.starts_with('<') {
+        (rest, _) = parse_angle_brackets(rest)?;
+    }
+
+    let type_name = &input[..input.len() - rest.len()];
+    if !type_name.contains("::") && !type_name.contains('<') {
+        return None;
+    }
+    Some((rest, type_name))
+}
+
+/// Parses balanced angle brackets and their contents verbatim, eg. generic arguments or
+/// opaque values like `<Future>` as output by types without a meaningful Debug output.
+fn parse_angle_brackets(input: &str) -> ParseResult<'_, &str> {
+    let contents = tag(input, "<")?;
+    let mut depth = 1;
+    let mut prev = '<';
+    let end = contents.char_indices().find_map(|(idx, c)| {
+        match c {
+            '<' => depth += 1,
+            // Skip the arrow of function pointer types, eg. `fn(u8) -> u8`.
+            '>' if prev != '-' => depth -= 1,
+            _ => (),
+        }
+        prev = c;
+        (depth == 0).then_some(idx)
+    })?;
+    let len = end + 2;
+    Some((&input[len..], &input[..len]))
+}
+
+/// Parses a char literal, eg. `'{'` or `'\''`, keeping it verbatim.
+fn parse_char(input: &str) -> ParseResult<'_, &str> {
+    let body = tag(input, "'")?;
+    let len = match body.strip_prefix('\\') {
+        // Escapes are at least two characters long, eg. `\'` or `\u{1b}`.
+        Some(escaped) => 1 + escaped.chars().next()?.len_utf8() + escaped[1..].find('\'')?,
+        None => body.chars().next()?.len_utf8(),
+    };
+    let rest = tag(&body[len..], "'")?;
+    Some((rest, &input[..len + 2]))
+}
+
+/// Parses a byte string literal, eg. `b"hi\n"` as output by the Debug implementation of
+/// `bytes::Bytes`, keeping it verbatim (including the escape sequences).
+fn parse_byte_string(input: &str) -> ParseResult<'_, &str> {
+    let mut chars = tag(input, "b\"")?.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => {
+                let len = idx + 3;
+                return Some((&input[len..], &input[..len]));
+            }
+            '\\' => {
+                chars.next()?;
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Parses a double-quoted string, unescaping the escape sequences that `str`'s Debug
+/// implementation produces.
+fn parse_string(input: &str) -> ParseResult<'_, String> {
+    let mut chars = tag(input, "\"")?.char_indices();
+    let body = &input[1..];
+    let mut string = String::new();
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Some((&body[idx + 1..], string)),
+            '\\' => match chars.next()?.1 {
+                'n' => string.push('\n'),
+                'r' => string.push('\r'),
+                't' => string.push('\t'),
+                'b' => string.push('\u{08}'),
+                'f' => string.push('\u{0C}'),
+                '0' => string.push('\0'),
+                '\\' => string.push('\\'),
+                '/' => string.push('/'),
+                '"' => string.push('"'),
+                '\'' => string.push('\''),
+                'u' => {
+                    let rest = tag(&body[idx + 2..], "{")?;
+                    let end = rest.find('}')?;
+                    let code = u32::from_str_radix(&rest[..end], 16).ok()?;
+                    string.push(char::from_u32(code)?);
+                    // Skip over the `{XXXX}` that was just consumed.
+                    for _ in 0..end + 2 {
+                        chars.next();
+                    }
+                }
+                // Escaped whitespace is discarded.
+                c if c.is_whitespace() => {
+                    while chars.clone().next().is_some_and(|(_, c)| c.is_whitespace()) {
+                        chars.next();
+                    }
+                }
+                _ => return None,
+            },
+            c => string.push(c),
+        }
+    }
+
+    None
+}
+
+fn parse_comma_separated_wrapped<'a, T>(
+    input: &'a str,
+    begin_wrap: &'static str,
+    end_wrap: &'static str,
+    parse_element: impl Fn(&'a str) -> ParseResult<'a, T>,
+) -> ParseResult<'a, Vec<T>> {
+    let mut input = consume_ws(tag(consume_ws(input), begin_wrap)?);
+    let mut values = Vec::new();
+
+    if let Some((rest, value)) = parse_element(input) {
+        values.push(value);
+        input = rest;
+        while let Some((rest, value)) = tag(consume_ws(input), ",").and_then(&parse_element) {
+            values.push(value);
+            input = rest;
+        }
+        // Allow a trailing comma, as output by `{:#?}` and single element tuples.
+        input = tag(consume_ws(input), ",").unwrap_or(input);
+    }
+
+    let rest = consume_ws(tag(consume_ws(input), end_wrap)?);
+    Some((rest, values))
+}
+
+fn tag<'a>(input: &'a str, tag: &str) -> Option<&'a str> {
+    input.strip_prefix(tag)
+}
+
+fn consume_ws(input: &str) -> &str {
+    input.trim_start()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    #[track_caller]
+    fn assert_same_debug<T: fmt::Debug>(item: &T) {
+        let parsed = parse(&format!("{:?}", item)).expect("can parse");
+        assert_eq!(format!("{:#?}", parsed), format!("{:#?}", item));
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", item));
+    }
+
+    #[test]
+    fn round_trips_std_types() {
+        #[derive(Debug)]
+        #[allow(unused)]
+        struct Foo {
+            value: f32,
+            bar: Vec<Bar>,
+            tuple: (&'static str, i32),
+        }
+
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Bar(i32);
+
+        assert_same_debug(&-2);
+        assert_same_debug(&());
+        assert_same_debug(&vec![(), ()]);
+        assert_same_debug(&f64::NEG_INFINITY);
+        assert_same_debug(&"Foo \"quoted\"\n\t\u{1b}");
+        assert_same_debug(&Foo {
+            value: 12.2,
+            bar: vec![Bar(200), Bar(-12)],
+            tuple: ("Foo", 32),
+        });
+        assert_same_debug(&{
+            let mut map = HashMap::new();
+            map.insert(Bar(2), "Foo");
+            map
+        });
+        assert_same_debug(&{
+            let mut set = HashSet::new();
+            set.insert("foo");
+            set
+        });
+    }
+
+    #[test]
+    fn tolerates_whitespace_and_trailing_commas() {
+        let expected = parse("Foo { a: [1, 2], b: (\"x\",) }").expect("can parse");
+        for input in [
+            "Foo{a:[1,2],b:(\"x\",)}",
+            "Foo { a : [ 1 , 2 ] , b : ( \"x\" , ) }",
+            "Foo {\n    a: [\n        1,\n        2,\n    ],\n    b: (\n        \"x\",\n    ),\n}",
+        ] {
+            assert_eq!(parse(input).expect("can parse"), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_flag_expressions_as_terms() {
+        let value = parse("MyFlags(A | B | 0x8)").expect("can parse");
+        assert_eq!(format!("{:?}", value), "MyFlags(A | B | 0x8)");
+        match value {
+            Value::Tuple(tuple) => assert!(matches!(
+                &tuple.values[..],
+                [Value::Term(Term::UnquotedRawString(flags))] if flags == "A | B | 0x8"
+            )),
+            _ => panic!("expected a tuple"),
+        }
+    }
+
+    #[test]
+    fn parses_explicit_discriminants_as_identifiers() {
+        let value = parse("[Low = 1, High = -2]").expect("can parse");
+        assert_eq!(format!("{:?}", value), "[Low = 1, High = -2]");
+        match value {
+            Value::List(list) => assert!(matches!(
+                &list.values[..],
+                [Value::Term(Term::Ident(low)), Value::Term(Term::Ident(high))]
+                    if low == "Low = 1" && high == "High = -2"
+            )),
+            _ => panic!("expected a list"),
+        }
+    }
+
+    #[test]
+    fn distinguishes_sets_from_maps() {
+        for (input, is_set) in [
+            ("{\"a\", \"b\"}", true),
+            ("{Foo { a: 1 }, Foo { a: 0 }}", true),
+            ("{(1, 2), Bar(3)}", true),
+            ("{\"a\": 1, \"b\": 2}", false),
+            ("{Foo { a: 1 }: [1]}", false),
+        ] {
+            match parse(input).expect("can parse") {
+                Value::Set(_) => assert!(is_set, "{}", input),
+                Value::Map(_) => assert!(!is_set, "{}", input),
+                value => panic!("expected a set or a map, got {:?}", value),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_special_floats_as_raw_terms() {
+        let value = parse("[inf, -inf, NaN, 1.5]").expect("can parse");
+        match value {
+            Value::List(list) => assert!(
+                list.values
+                    .iter()
+                    .all(|value| matches!(value, Value::Term(Term::UnquotedRawString(_)))),
+                "{:?}",
+                list
+            ),
+            _ => panic!("expected a list"),
+        }
+        assert_same_debug(&[f64::INFINITY, f64::NEG_INFINITY, f64::NAN]);
+    }
+
+    #[test]
+    fn keeps_values_nested_too_deeply_verbatim() {
+        let options = ParseOptions {
+            max_depth: 1,
+            ..ParseOptions::default()
+        };
+        let value = parse_with_options(r#"[Foo { a: {"b": ")]}", "a": '}'} }, 1]"#, options)
+            .expect("can parse");
+        match value {
+            Value::List(list) => assert!(
+                matches!(
+                    &list.values[..],
+                    [Value::Term(Term::UnquotedRawString(nested)), Value::Term(_)]
+                        if nested == r#"Foo { a: {"b": ")]}", "a": '}'} }"#
+                ),
+                "{:?}",
+                list
+            ),
+            _ => panic!("expected a list"),
+        }
+        // Nothing is printed unless the warning is enabled.
+        assert!(!WARNED_MAX_DEPTH.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn keeps_map_values_verbatim() {
+        let options = ParseOptions {
+            verbatim_map_values: true,
+            ..ParseOptions::default()
+        };
+        let value = parse_with_options(r#"{"b": Blob( 1 ,  2 ), "a":  "x" , "c": 0x1}"#, options)
+            .expect("can parse");
+        match value {
+            Value::Map(map) => assert_eq!(
+                map.values
+                    .iter()
+                    .map(|key_value| match &key_value.value {
+                        Value::Term(Term::UnquotedRawString(verbatim)) => verbatim.as_str(),
+                        value => panic!("expected a raw string term, got {:?}", value),
+                    })
+                    .collect::<Vec<_>>(),
+                ["Blob( 1 ,  2 )", "\"x\"", "0x1"]
+            ),
+            _ => panic!("expected a map"),
+        }
+    }
+
+    #[test]
+    fn parses_type_names_as_terms() {
+        for type_name in [
+            "MaybeUninit<u8>",
+            "MaybeUninit<std::collections::hash::map::HashMap<&str, i32>>",
+            "PhantomData<fn(u8) -> Vec<u8>>",
+            "core::marker::PhantomPinned",
+        ] {
+            let value = parse(&format!("[{}]", type_name)).expect("can parse");
+            match value {
+                Value::List(list) => assert!(
+                    matches!(
+                        &list.values[..],
+                        [Value::Term(Term::UnquotedRawString(term))] if term == type_name
+                    ),
+                    "{}",
+                    type_name
+                ),
+                _ => panic!("expected a list"),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_type_names_as_struct_and_tuple_names() {
+        let value = parse(
+            "[Wrapper<HashMap<String, Vec<(u8, u16)>>, u32> { a: 1 }, alloc::rc::Weak<u8>(1)]",
+        )
+        .expect("can parse");
+        match value {
+            Value::List(list) => match &list.values[..] {
+                [Value::Struct(s), Value::Tuple(t)] => {
+                    assert_eq!(s.name, "Wrapper<HashMap<String, Vec<(u8, u16)>>, u32>");
+                    assert_eq!(t.name.as_deref(), Some("alloc::rc::Weak<u8>"));
+                }
+                values => panic!("expected a struct and a tuple, got {:?}", values),
+            },
+            _ => panic!("expected a list"),
+        }
+    }
+
+    #[test]
+    fn parses_angle_bracketed_opaque_values_as_terms() {
+        for opaque in ["<opaque>", "<Future at 0x1f, {pending}>", "<Box<dyn Fn()>>"] {
+            let value = parse(&format!("{{\"a\": {}}}", opaque)).expect("can parse");
+            match value {
+                Value::Map(map) => assert!(
+                    matches!(
+                        &map.values[0].value,
+                        Value::Term(Term::UnquotedRawString(term)) if term == opaque
+                    ),
+                    "{}",
+                    opaque
+                ),
+                _ => panic!("expected a map"),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_truncation_markers_as_terms() {
+        let value =
+            parse(r#"{"a": ..., "b": "abc"..., "c": abc..., "d": [1, ...]}"#).expect("can parse");
+        assert_eq!(
+            format!("{:?}", value),
+            r#"{"a": ..., "b": "abc"..., "c": abc..., "d": [1, ...]}"#
+        );
+
+        // Neither a truncation marker nor the non-exhaustive marker is a struct field.
+        assert!(parse("Foo { a: 1, ... }").is_err());
+        assert!(parse("Foo { a: 1, .. }").is_ok());
+    }
+
+    #[test]
+    fn normalizes_decimal_commas() {
+        assert_eq!(
+            normalize_decimal_commas(r#"Foo { a: 1,5, b: [-0,25, 3], c: "1,5", d: ',' }"#),
+            r#"Foo { a: 1.5, b: [-0.25, 3], c: "1,5", d: ',' }"#
+        );
+    }
+
+    #[test]
+    fn parses_byte_strings_as_terms() {
+        let value = parse(r#"{"a": b"h\"i\x00"}"#).expect("can parse");
+        assert_eq!(format!("{:?}", value), r#"{"a": b"h\"i\x00"}"#);
+    }
+
+    #[test]
+    fn parses_char_literals_as_terms() {
+        assert_same_debug(&vec!['{', ',', ':', '\'', '"', ' ', '\n', '\u{1b}', 'ß']);
+    }
+
+    #[test]
+    fn reports_unconsumed_input() {
+        let err = parse("Object {\"a\": Number(0)}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to consume all of string!\nValue:\nObject\n\nRest:\n\" {\\\"a\\\": Number(0)}\""
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! Sorting of the collections in a parsed [`Value`] tree.
+use std::cmp::Ordering;
+
+use darrentsung_debug_parser::{KeyValue, OrNonExhaustive, Term, Value};
+
+use crate::normalize::float;
+use crate::{FieldKind, SortConfig};
+
+/// Sorts the maps and sets (and, depending on `config`, lists and tuples) in `v`. With
+/// [`SortConfig::unordered_only`], only the maps and sets in the listed fields are sorted,
+/// and the collections in the fields listed in [`SortConfig::blocklist`] are never sorted.
+///
+/// Children are sorted before their parent so that the parent is ordered by the
+/// canonical (sorted) form of its elements. Elements are ordered by variant first
+/// (structs, sets, maps, lists, tuples, then terms) and then by content, which means enum
+/// variants are grouped by name before their payloads are compared, eg. `Err(..)` sorts
+/// before `Ok(..)`.
+pub(crate) fn sort_maps(v: &mut Value, config: &SortConfig) {
+    sort_value(
+        v,
+        config,
+        config.sort_sequences,
+        config.unordered_only.is_empty(),
+    );
+}
+
+/// Sorts `v` like [`sort_maps`], where `sort_sequences` decides whether `v` itself is
+/// sorted if it's a list or tuple, and `sort_unordered` if it's a map or set.
+fn sort_value(v: &mut Value, config: &SortConfig, sort_sequences: bool, sort_unordered: bool) {
+    match v {
+        Value::Struct(s) => {
+            for ident_value_or_non_exhaustive in &mut s.values {
+                match ident_value_or_non_exhaustive {
+                    OrNonExhaustive::Value(ident_value) => {
+                        let sort_sequences = config
+                            .schema
+                            .iter()
+                            .find(|(field, _)| *field == ident_value.ident)
+                            .map_or(config.sort_sequences, |(_, kind)| {
+                                *kind == FieldKind::Unordered
+                            });
+                        let sort_unordered = config.unordered_only.is_empty()
+                            || config.unordered_only.contains(&ident_value.ident);
+                        let blocked = config.blocklist.contains(&ident_value.ident);
+                        sort_value(
+                            &mut ident_value.value,
+                            config,
+                            sort_sequences && !blocked,
+                            sort_unordered && !blocked,
+                        );
+                    }
+                    OrNonExhaustive::NonExhaustive => (),
+                }
+            }
+            if config.sort_struct_fields || !config.field_order.is_empty() {
+                // Listed fields come first, in the configured order.
+                let field_rank = |ident: &str| {
+                    config
+                        .field_order
+                        .iter()
+                        .position(|field| field == ident)
+                        .unwrap_or(config.field_order.len())
+                };
+                // The sort is stable and `NonExhaustive` orders after any field, so the `..`
+                // marker stays last.
+                s.values.sort_by(|a, b| match (a, b) {
+                    (OrNonExhaustive::Value(a), OrNonExhaustive::Value(b)) => field_rank(&a.ident)
+                        .cmp(&field_rank(&b.ident))
+                        .then_with(|| a.ident.cmp(&b.ident)),
+                    (a, b) => a.cmp(b),
+                });
+            }
+        }
+        Value::Set(s) => {
+            for child_v in &mut s.values {
+                sort_maps(child_v, config);
+            }
+            if sort_unordered {
+                s.values.sort_by(|a, b| compare_values(a, b, config));
+            }
+        }
+        Value::Map(map) => {
+            for key_value in &mut map.values {
+                sort_maps(&mut key_value.key, config);
+                match &key_value.key {
+                    Value::Term(Term::String(key)) if config.blocklist.contains(key) => {
+                        sort_value(&mut key_value.value, config, false, false)
+                    }
+                    _ => sort_maps(&mut key_value.value, config),
+                }
+            }
+            // Keys were canonicalized above, so composite keys (eg. lists of structs
+            // containing maps) order deterministically. Equal keys (eg. after being
+            // normalized with `canonical_keys`, or duplicated in a multimap's Debug output)
+            // are ordered by their value.
+            if sort_unordered {
+                map.values.sort_by(|a, b| compare_entries(a, b, config));
+            }
+        }
+        Value::List(l) => {
+            for child_v in &mut l.values {
+                sort_maps(child_v, config);
+            }
+            if sort_sequences {
+                l.values.sort_by(|a, b| compare_values(a, b, config));
+            }
+        }
+        Value::Tuple(t) => {
+            for child_v in &mut t.values {
+                sort_maps(child_v, config);
+            }
+            if sort_sequences {
+                t.values.sort_by(|a, b| compare_values(a, b, config));
+            }
+        }
+        // No need to recurse for Term variant.
+        Value::Term(_) => (),
+    }
+}
+
+/// Orders two map entries by key and then by value, or the other way around with
+/// [`SortConfig::sort_by_value`].
+pub(crate) fn compare_entries(a: &KeyValue, b: &KeyValue, config: &SortConfig) -> Ordering {
+    let by_key = || compare_values(&a.key, &b.key, config);
+    let by_value = || compare_values(&a.value, &b.value, config);
+    if config.sort_by_value {
+        by_value().then_with(by_key)
+    } else {
+        by_key().then_with(by_value)
+    }
+}
+
+/// Orders two values the same way as their derived `Ord` implementation, but consults
+/// `config` for custom orderings, starting with [`SortConfig::comparator`].
+///
+/// This is a total order over all terms: identifiers (eg. `false`, `true` and unit
+/// variants) sort before strings, which sort before raw terms (eg. numbers). Terms of the
+/// same kind are compared by their text, so `false` sorts before `true` and numbers sort
+/// lexicographically unless [`SortConfig::natural_order`] is set. The exception are
+/// durations (eg. `1.5s` and `250ms`), which sort chronologically before other raw terms,
+/// and the special floats: `-inf` sorts before and `inf` and `NaN` after other raw terms.
+///
+/// Enum variants are ordered by their shape first (struct variants, then tuple variants,
+/// then unit variants), then by name and then by payload, eg. `A(1) < A(2) < B`. Every
+/// value of a variant has the same shape, so the values of each variant stay grouped.
+///
+/// Values that the config considers equal (eg. differently cased strings with
+/// [`SortConfig::case_insensitive_keys`]) are ordered by their content, so only identical
+/// values compare equal and the sorted output never depends on the input order. The
+/// exception are floats within [`SortConfig::float_tolerance`] of each other, which
+/// compare equal regardless of their text.
+pub(crate) fn compare_values(a: &Value, b: &Value, config: &SortConfig) -> Ordering {
+    if let Some(comparator) = &config.comparator {
+        let ordering = comparator(a, b);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    if let Some(variant_rank) = &config.variant_rank {
+        if let (Some(a_name), Some(b_name)) = (variant_name(a), variant_name(b)) {
+            let ordering = variant_rank(a_name).cmp(&variant_rank(b_name));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+    }
+
+    match (a, b) {
+        (Value::Struct(a), Value::Struct(b)) => a.name.cmp(&b.name).then_with(|| {
+            compare_slices(&a.values, &b.values, |a, b| match (a, b) {
+                (OrNonExhaustive::Value(a), OrNonExhaustive::Value(b)) => a
+                    .ident
+                    .cmp(&b.ident)
+                    .then_with(|| compare_values(&a.value, &b.value, config)),
+                (a, b) => a.cmp(b),
+            })
+        }),
+        (Value::Set(a), Value::Set(b)) => {
+            compare_slices(&a.values, &b.values, |a, b| compare_values(a, b, config))
+        }
+        (Value::Map(a), Value::Map(b)) => compare_slices(&a.values, &b.values, |a, b| {
+            compare_values(&a.key, &b.key, config)
+                .then_with(|| compare_values(&a.value, &b.value, config))
+        }),
+        (Value::List(a), Value::List(b)) => {
+            compare_slices(&a.values, &b.values, |a, b| compare_values(a, b, config))
+        }
+        (Value::Tuple(a), Value::Tuple(b)) => a.name.cmp(&b.name).then_with(|| {
+            compare_slices(&a.values, &b.values, |a, b| {
+                let numeric_ordering = match (a, b) {
+                    (Value::Term(a), Value::Term(b)) if config.sort_tuples => compare_numbers(a, b),
+                    _ => None,
+                };
+                numeric_ordering.unwrap_or_else(|| compare_values(a, b, config))
+            })
+        }),
+        (Value::Term(a), Value::Term(b)) => compare_terms(a, b, config),
+        (a, b) => variant_index(a).cmp(&variant_index(b)),
+    }
+}
+
+fn compare_terms(a: &Term, b: &Term, config: &SortConfig) -> Ordering {
+    if let (Term::UnquotedRawString(a), Term::UnquotedRawString(b)) = (a, b) {
+        if floats_within_tolerance(a, b, config) {
+            return Ordering::Equal;
+        }
+    }
+    let (a_text, b_text) = (term_text(a), term_text(b));
+    let ordering = match (a, b) {
+        (Term::String(_), Term::String(_)) if config.case_insensitive_keys => {
+            compare_text(&a_text.to_lowercase(), &b_text.to_lowercase(), config)
+        }
+        (Term::UnquotedRawString(_), Term::UnquotedRawString(_)) => {
+            // Durations sort before other raw terms, chronologically.
+            match (duration_nanos(a_text), duration_nanos(b_text)) {
+                (Some(a_nanos), Some(b_nanos)) => a_nanos.cmp(&b_nanos),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => special_float_rank(a_text)
+                    .cmp(&special_float_rank(b_text))
+                    .then_with(|| compare_text(a_text, b_text, config)),
+            }
+        }
+        (Term::Ident(_), Term::Ident(_)) | (Term::String(_), Term::String(_)) => {
+            compare_text(a_text, b_text, config)
+        }
+        // Different kinds of terms are ordered by their kind.
+        _ => a.cmp(b),
+    };
+
+    // Fall back to the derived order so that terms that only compare equal because of
+    // the config (eg. differently cased strings) are still ordered deterministically.
+    ordering.then_with(|| a.cmp(b))
+}
+
+/// Whether `a` and `b` are floats that differ by at most [`SortConfig::float_tolerance`].
+pub(crate) fn floats_within_tolerance(a: &str, b: &str, config: &SortConfig) -> bool {
+    match (config.float_tolerance, float(a), float(b)) {
+        (Some(tolerance), Some(a), Some(b)) => (a - b).abs() <= tolerance,
+        _ => false,
+    }
+}
+
+/// Compares two terms by their numeric value, if they're both numbers. Numbers that are
+/// equal in value fall back to their text, eg. for `1.0` and `1`.
+fn compare_numbers(a: &Term, b: &Term) -> Option<Ordering> {
+    match (a, b) {
+        (Term::UnquotedRawString(a), Term::UnquotedRawString(b)) => {
+            let (a_num, b_num) = (a.parse::<f64>().ok()?, b.parse::<f64>().ok()?);
+            Some(a_num.total_cmp(&b_num).then_with(|| a.cmp(b)))
+        }
+        _ => None,
+    }
+}
+
+/// Ranks the special floats around other raw terms: `-inf` sorts first, `inf` and then
+/// `NaN` sort last.
+fn special_float_rank(text: &str) -> u8 {
+    match text {
+        "-inf" => 0,
+        "inf" => 2,
+        "NaN" => 3,
+        _ => 1,
+    }
+}
+
+/// Parses the Debug output of a [`Duration`](std::time::Duration), eg. `1.5s` or `250ms`,
+/// into its total number of nanoseconds.
+fn duration_nanos(text: &str) -> Option<u128> {
+    let (number, unit_nanos) = [
+        ("ns", 1),
+        ("µs", 1_000),
+        ("ms", 1_000_000),
+        ("s", 1_000_000_000),
+    ]
+    .into_iter()
+    .find_map(|(suffix, unit_nanos)| Some((text.strip_suffix(suffix)?, unit_nanos)))?;
+    let (integral, fractional) = number.split_once('.').unwrap_or((number, ""));
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if integral.is_empty() || !is_digits(integral) || !is_digits(fractional) {
+        return None;
+    }
+
+    let mut nanos = integral.parse::<u128>().ok()?.checked_mul(unit_nanos)?;
+    // Each fractional digit is worth a tenth of the previous one.
+    let mut digit_nanos = unit_nanos;
+    for digit in fractional.bytes() {
+        digit_nanos /= 10;
+        nanos += u128::from(digit - b'0') * digit_nanos;
+    }
+    Some(nanos)
+}
+
+fn compare_text(a: &str, b: &str, config: &SortConfig) -> Ordering {
+    if config.natural_order {
+        natural_cmp(a, b)
+    } else {
+        a.cmp(b)
+    }
+}
+
+/// Compares strings chunk by chunk, where runs of ASCII digits are compared by their
+/// numeric value and everything else lexically.
+fn natural_cmp(mut a: &str, mut b: &str) -> Ordering {
+    loop {
+        let (a_chunk, a_rest) = split_chunk(a);
+        let (b_chunk, b_rest) = split_chunk(b);
+        let ordering = match (a_chunk, b_chunk) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_chunk), Some(b_chunk)) => {
+                if is_digits(a_chunk) && is_digits(b_chunk) {
+                    let a_num = a_chunk.trim_start_matches('0');
+                    let b_num = b_chunk.trim_start_matches('0');
+                    a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num))
+                } else {
+                    a_chunk.cmp(b_chunk)
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        a = a_rest;
+        b = b_rest;
+    }
+}
+
+/// Splits off the leading run of digits or non-digits.
+fn split_chunk(s: &str) -> (Option<&str>, &str) {
+    let Some(first) = s.chars().next() else {
+        return (None, s);
+    };
+    let len = s
+        .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+        .unwrap_or(s.len());
+    (Some(&s[..len]), &s[len..])
+}
+
+fn is_digits(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// The text of a term, without the surrounding quotation marks of strings.
+fn term_text(term: &Term) -> &str {
+    match term {
+        Term::Ident(s) | Term::String(s) | Term::UnquotedRawString(s) => s,
+    }
+}
+
+/// Lexicographically compares two slices with `compare`.
+fn compare_slices<T>(a: &[T], b: &[T], mut compare: impl FnMut(&T, &T) -> Ordering) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare(a, b))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// Position of the variant in the [`Value`] enum, which is used to order different kinds
+/// of values relative to each other.
+fn variant_index(v: &Value) -> u8 {
+    match v {
+        Value::Struct(_) => 0,
+        Value::Set(_) => 1,
+        Value::Map(_) => 2,
+        Value::List(_) => 3,
+        Value::Tuple(_) => 4,
+        Value::Term(_) => 5,
+    }
+}
+
+/// The name of the enum variant (or type) that `v` represents, if any.
+fn variant_name(v: &Value) -> Option<&str> {
+    match v {
+        Value::Struct(s) => Some(&s.name),
+        Value::Tuple(t) => t.name.as_deref(),
+        Value::Term(Term::Ident(ident)) => Some(ident),
+        _ => None,
+    }
+}
@@ -0,0 +1,431 @@
+//! Matching sorted Debug output against a template with `{{regex}}` placeholders, see
+//! [`assert_matches_sorted`](crate::assert_matches_sorted).
+//!
+//! Only a subset of regex syntax is supported: literals, `.`, character classes (`[a-f0-9]`,
+//! `[^,]`), the escapes `\d`, `\w`, `\s` (and their negations), `\n`, `\t` and escaped
+//! punctuation (eg. `\}` or `\.`), groups with alternation (`(a|b)`, `(?:a|b)`) and the
+//! quantifiers `*`, `+`, `?` and `{n}`, `{n,}`, `{n,m}`. Anything else (eg. anchors,
+//! lookarounds, backreferences or lazy quantifiers) is reported as an error rather than
+//! silently matched literally. A placeholder always has to match completely, as if it was
+//! anchored.
+//!
+//! Outside of placeholders, `{{{{` matches a literal `{{`.
+//!
+//! Placeholders are matched by following all possible paths through the template at once,
+//! so matching takes time linear in the size of the output and can't backtrack
+//! excessively.
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A single character matcher.
+enum Atom {
+    /// Any character except a newline (`.`).
+    Any,
+    Literal(char),
+    /// `\d`
+    Digit,
+    /// `\w`
+    Word,
+    /// `\s`
+    Space,
+    /// `\D`, `\W`, `\S` or a negated character class.
+    Not(Box<Atom>),
+    /// A character class, matches if any of its items match.
+    Class(Vec<Atom>),
+    Range(char, char),
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Any => c != '\n',
+            Atom::Literal(literal) => c == *literal,
+            Atom::Digit => c.is_ascii_digit(),
+            Atom::Word => c.is_alphanumeric() || c == '_',
+            Atom::Space => c.is_whitespace(),
+            Atom::Not(atom) => !atom.matches(c),
+            Atom::Class(atoms) => atoms.iter().any(|atom| atom.matches(c)),
+            Atom::Range(start, end) => (*start..=*end).contains(&c),
+        }
+    }
+}
+
+enum Node {
+    Atom(Atom),
+    /// Alternatives, each a sequence of nodes.
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// Returns whether `actual` matches `template`, where the text outside of `{{regex}}`
+/// placeholders has to match exactly. Returns an error if a placeholder isn't a valid
+/// regex.
+pub(crate) fn matches_template(template: &str, actual: &str) -> Result<bool, String> {
+    let nodes = parse_template(template)?;
+    let mut program = vec![];
+    compile_sequence(&nodes, &mut program);
+    program.push(Inst::Match);
+    Ok(run(&program, actual))
+}
+
+fn parse_template(template: &str) -> Result<Vec<Node>, String> {
+    let mut chars = template.chars().peekable();
+    let mut nodes = vec![];
+    while let Some(c) = chars.next() {
+        if c == '{' && chars.peek() == Some(&'{') {
+            chars.next();
+            if chars.clone().take(2).eq(['{', '{']) {
+                chars.nth(1);
+                nodes.push(Node::Atom(Atom::Literal('{')));
+                nodes.push(Node::Atom(Atom::Literal('{')));
+                continue;
+            }
+            nodes.push(parse_alternatives(&mut chars, false)?);
+        } else {
+            nodes.push(Node::Atom(Atom::Literal(c)));
+        }
+    }
+    Ok(nodes)
+}
+
+/// Parses alternatives up to the end of a group (`)`) or of a placeholder (`}}`).
+fn parse_alternatives(chars: &mut Peekable<Chars>, in_group: bool) -> Result<Node, String> {
+    let mut alternatives = vec![vec![]];
+    loop {
+        match chars.peek() {
+            None if in_group => return Err("unclosed group".to_string()),
+            None => return Err("unterminated placeholder, expected `}}`".to_string()),
+            Some('}') if !in_group => {
+                chars.next();
+                if chars.next() != Some('}') {
+                    return Err("unexpected `}`, expected `}}`".to_string());
+                }
+                break;
+            }
+            Some(')') if in_group => {
+                chars.next();
+                break;
+            }
+            Some('|') => {
+                chars.next();
+                alternatives.push(vec![]);
+            }
+            Some(_) => {
+                let node = parse_node(chars)?;
+                let node = parse_quantifier(chars, node)?;
+                alternatives
+                    .last_mut()
+                    .expect("there's always an alternative")
+                    .push(node);
+            }
+        }
+    }
+    Ok(Node::Group(alternatives))
+}
+
+fn parse_node(chars: &mut Peekable<Chars>) -> Result<Node, String> {
+    let atom = match chars.next() {
+        Some('(') => {
+            // Non-capturing groups behave the same, nothing is captured anyway.
+            if chars.peek() == Some(&'?') {
+                chars.next();
+                if chars.next() != Some(':') {
+                    return Err("unsupported group syntax".to_string());
+                }
+            }
+            return parse_alternatives(chars, true);
+        }
+        Some('.') => Atom::Any,
+        Some('[') => parse_class(chars)?,
+        Some('\\') => parse_escape(chars)?,
+        Some(c @ ('*' | '+' | '?' | '{')) => {
+            return Err(format!("nothing to repeat before `{}`", c))
+        }
+        Some(')') => return Err("unmatched `)`".to_string()),
+        Some(c @ ('^' | '$')) => return Err(format!("unsupported anchor `{}`", c)),
+        Some(c) => Atom::Literal(c),
+        None => unreachable!("only called when there's a character left"),
+    };
+    Ok(Node::Atom(atom))
+}
+
+fn parse_escape(chars: &mut Peekable<Chars>) -> Result<Atom, String> {
+    Ok(match chars.next() {
+        Some('d') => Atom::Digit,
+        Some('w') => Atom::Word,
+        Some('s') => Atom::Space,
+        Some('D') => Atom::Not(Box::new(Atom::Digit)),
+        Some('W') => Atom::Not(Box::new(Atom::Word)),
+        Some('S') => Atom::Not(Box::new(Atom::Space)),
+        Some('n') => Atom::Literal('\n'),
+        Some('t') => Atom::Literal('\t'),
+        Some(c) if c.is_alphanumeric() => return Err(format!("unsupported escape `\\{}`", c)),
+        Some(c) => Atom::Literal(c),
+        None => return Err("trailing backslash".to_string()),
+    })
+}
+
+fn parse_class(chars: &mut Peekable<Chars>) -> Result<Atom, String> {
+    let negated = chars.peek() == Some(&'^');
+    if negated {
+        chars.next();
+    }
+
+    let mut atoms = vec![];
+    loop {
+        let atom = match chars.next() {
+            None => return Err("unclosed character class".to_string()),
+            Some(']') => break,
+            Some('\\') => parse_escape(chars)?,
+            Some(start) => {
+                // A `-` at the end of the class is a literal, eg. `[a-]`.
+                let mut lookahead = chars.clone();
+                match (lookahead.next(), lookahead.next()) {
+                    (Some('-'), Some(end)) if end != ']' => {
+                        chars.next();
+                        chars.next();
+                        Atom::Range(start, end)
+                    }
+                    _ => Atom::Literal(start),
+                }
+            }
+        };
+        atoms.push(atom);
+    }
+
+    let class = Atom::Class(atoms);
+    Ok(if negated {
+        Atom::Not(Box::new(class))
+    } else {
+        class
+    })
+}
+
+fn parse_quantifier(chars: &mut Peekable<Chars>, node: Node) -> Result<Node, String> {
+    let (min, max) = match chars.peek() {
+        Some('*') => (0, None),
+        Some('+') => (1, None),
+        Some('?') => (0, Some(1)),
+        Some('{') => {
+            chars.next();
+            let min = parse_count(chars).ok_or("invalid repetition count")?;
+            let max = match chars.next() {
+                Some('}') => return Ok(repeat(node, min, Some(min))),
+                Some(',') if chars.peek() == Some(&'}') => None,
+                Some(',') => Some(parse_count(chars).ok_or("invalid repetition count")?),
+                _ => return Err("invalid repetition".to_string()),
+            };
+            if chars.next() != Some('}') {
+                return Err("invalid repetition".to_string());
+            }
+            if max.is_some_and(|max| max < min) {
+                return Err("invalid repetition count".to_string());
+            }
+            return Ok(repeat(node, min, max));
+        }
+        _ => return Ok(node),
+    };
+    chars.next();
+    Ok(repeat(node, min, max))
+}
+
+fn parse_count(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits.parse().ok()
+}
+
+fn repeat(node: Node, min: usize, max: Option<usize>) -> Node {
+    Node::Repeat {
+        node: Box::new(node),
+        min,
+        max,
+    }
+}
+
+/// An instruction of the compiled template, executed by [`run`].
+#[derive(Clone, Copy)]
+enum Inst<'a> {
+    /// Consumes a character matching the atom.
+    Char(&'a Atom),
+    /// Continues at both instructions.
+    Split(usize, usize),
+    Jump(usize),
+    /// The whole input has been matched, if it's been consumed.
+    Match,
+}
+
+fn compile_sequence<'a>(nodes: &'a [Node], program: &mut Vec<Inst<'a>>) {
+    for node in nodes {
+        compile_node(node, program);
+    }
+}
+
+fn compile_node<'a>(node: &'a Node, program: &mut Vec<Inst<'a>>) {
+    match node {
+        Node::Atom(atom) => program.push(Inst::Char(atom)),
+        Node::Group(alternatives) => {
+            // Each alternative but the last is preceded by a split to the next one, and
+            // jumps past the others once it's matched.
+            let mut jumps = vec![];
+            for (idx, nodes) in alternatives.iter().enumerate() {
+                if idx + 1 == alternatives.len() {
+                    compile_sequence(nodes, program);
+                    break;
+                }
+                let split = placeholder(program);
+                compile_sequence(nodes, program);
+                jumps.push(placeholder(program));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile_node(node, program);
+            }
+            match max {
+                None => {
+                    let split = placeholder(program);
+                    compile_node(node, program);
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let splits: Vec<_> = (*min..*max)
+                        .map(|_| {
+                            let split = placeholder(program);
+                            compile_node(node, program);
+                            split
+                        })
+                        .collect();
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reserves an instruction that's filled in once its targets are known.
+fn placeholder(program: &mut Vec<Inst>) -> usize {
+    program.push(Inst::Match);
+    program.len() - 1
+}
+
+/// Runs `program` against all of `input`, following every possible path at once (a
+/// Thompson NFA). Unlike a backtracking matcher, this doesn't recurse per character and
+/// takes time linear in the size of the input.
+fn run(program: &[Inst], input: &str) -> bool {
+    let mut seen = vec![false; program.len()];
+    let mut threads = vec![];
+    add_thread(program, 0, &mut threads, &mut seen);
+
+    let mut next = vec![];
+    for c in input.chars() {
+        seen.fill(false);
+        for &pc in &threads {
+            if matches!(program[pc], Inst::Char(atom) if atom.matches(c)) {
+                add_thread(program, pc + 1, &mut next, &mut seen);
+            }
+        }
+        std::mem::swap(&mut threads, &mut next);
+        next.clear();
+        if threads.is_empty() {
+            return false;
+        }
+    }
+    threads.iter().any(|&pc| matches!(program[pc], Inst::Match))
+}
+
+/// Adds the instructions reachable from `pc` without consuming a character to `threads`.
+fn add_thread(program: &[Inst], pc: usize, threads: &mut Vec<usize>, seen: &mut [bool]) {
+    let mut stack = vec![pc];
+    while let Some(pc) = stack.pop() {
+        if std::mem::replace(&mut seen[pc], true) {
+            continue;
+        }
+        match program[pc] {
+            Inst::Split(first, second) => stack.extend([second, first]),
+            Inst::Jump(to) => stack.push(to),
+            Inst::Char(_) | Inst::Match => threads.push(pc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_placeholders() {
+        for (template, actual) in [
+            ("{ id: {{\\d+}} }", "{ id: 42 }"),
+            ("\"{{[0-9a-f]{4}-[0-9a-f]{2,}}}\"", "\"1a2b-3c4d5e\""),
+            ("{{(Some|None)}}({{.*}})", "Some(1)"),
+            ("[{{[^,]*}}, 2]", "[, 2]"),
+            ("{{(ab)?c}}", "c"),
+            ("{{(a*)*b}}", "aaab"),
+            ("{{(a|ab)(c|bcd)}}", "abcd"),
+            ("{{a{2,3}}}", "aaa"),
+            ("{{{{ literal }}", "{{ literal }}"),
+            ("{{{{{{\\d}}}}", "{{1}}"),
+            ("{{\\{\\}\\$\\.}}", "{}$."),
+        ] {
+            assert_eq!(matches_template(template, actual), Ok(true), "{}", template);
+        }
+    }
+
+    #[test]
+    fn rejects_mismatches() {
+        for (template, actual) in [
+            ("{ id: {{\\d+}} }", "{ id: abc }"),
+            ("{{\\d{3}}}", "1234"),
+            ("{{.*}}", "a\nb"),
+            ("Foo", "Bar"),
+            ("{{a{2,3}}}", "aaaa"),
+            ("Foo", "Foo "),
+        ] {
+            assert_eq!(
+                matches_template(template, actual),
+                Ok(false),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn reports_invalid_placeholders() {
+        for (template, err) in [
+            ("{{\\d+", "unterminated placeholder, expected `}}`"),
+            ("{{(a}}", "unclosed group"),
+            ("{{[a-z}}", "unclosed character class"),
+            ("{{*}}", "nothing to repeat before `*`"),
+            ("{{a{3,2}}}", "invalid repetition count"),
+            ("{{^a}}", "unsupported anchor `^`"),
+            ("{{a$}}", "unsupported anchor `$`"),
+            ("{{(?=a)}}", "unsupported group syntax"),
+            ("{{(a)\\1}}", "unsupported escape `\\1`"),
+            ("{{a*?}}", "nothing to repeat before `?`"),
+        ] {
+            assert_eq!(
+                matches_template(template, ""),
+                Err(err.to_string()),
+                "{}",
+                template
+            );
+        }
+    }
+}
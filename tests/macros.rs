@@ -5,4 +5,308 @@ mod assert_eq {
         let a = "some value";
         ::pretty_assertions_sorted::assert_eq_sorted!(a, a);
     }
+
+    #[test]
+    fn passes_for_primitives() {
+        ::pretty_assertions_sorted::assert_eq_sorted!(5, 5);
+        ::pretty_assertions_sorted::assert_eq_sorted!(-2.5, -2.5);
+        ::pretty_assertions_sorted::assert_eq_sorted!(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        ::pretty_assertions_sorted::assert_eq_sorted!(true, true);
+        ::pretty_assertions_sorted::assert_eq_sorted!('x', 'x');
+        ::pretty_assertions_sorted::assert_eq_sorted!("a: {b}, [c]", "a: {b}, [c]");
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`")]
+    fn fails_for_different_integers() {
+        ::pretty_assertions_sorted::assert_eq_sorted!(5, 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`: custom message")]
+    fn fails_for_different_strings() {
+        ::pretty_assertions_sorted::assert_eq_sorted!("a: {b}", "a: {c}", "custom message");
+    }
+}
+
+mod assert_ne {
+    use std::collections::HashMap;
+
+    #[test]
+    fn passes_for_different_values() {
+        let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        let right: HashMap<_, _> = [("b", 3), ("a", 1)].into_iter().collect();
+        ::pretty_assertions_sorted::assert_ne_sorted!(left, right);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left != right)`")]
+    fn fails_for_values_that_are_equal_after_sorting() {
+        let left: HashMap<_, _> = (0..20).map(|i| (i, i)).collect();
+        let right: HashMap<_, _> = (0..20).rev().map(|i| (i, i)).collect();
+        ::pretty_assertions_sorted::assert_ne_sorted!(left, right);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: `(left != right)`: custom message\n\nBoth sides:\n5\n"
+    )]
+    fn fails_with_custom_message() {
+        ::pretty_assertions_sorted::assert_ne_sorted!(5, 5, "custom message");
+    }
+}
+
+mod assert_eq_lazy {
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    #[test]
+    fn calls_right_hand_side_once() {
+        let calls = Cell::new(0);
+        let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        ::pretty_assertions_sorted::assert_eq_sorted_lazy!(left, || {
+            calls.set(calls.get() + 1);
+            [("b", 2), ("a", 1)].into_iter().collect::<HashMap<_, _>>()
+        });
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to parse Debug output for sorting")]
+    fn skips_right_hand_side_when_left_cant_be_parsed() {
+        struct Unparseable;
+
+        impl std::fmt::Debug for Unparseable {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "Object {{\"a\": Number(0)}}")
+            }
+        }
+
+        ::pretty_assertions_sorted::assert_eq_sorted_lazy!(Unparseable, || -> &str {
+            unreachable!("the right-hand side isn't needed")
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`: custom message")]
+    fn fails_for_different_values() {
+        ::pretty_assertions_sorted::assert_eq_sorted_lazy!(5, || 6, "custom message");
+    }
+}
+
+mod assert_keys_eq {
+    use std::collections::HashMap;
+
+    #[test]
+    fn passes_for_same_keys_with_different_values() {
+        let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        let right: HashMap<_, _> = [("b", 20), ("a", 10)].into_iter().collect();
+        ::pretty_assertions_sorted::assert_keys_eq_sorted!(left, right);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(left == right)`: custom message")]
+    fn fails_for_different_keys() {
+        let left: HashMap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+        let right: HashMap<_, _> = [("a", 1), ("c", 2)].into_iter().collect();
+        ::pretty_assertions_sorted::assert_keys_eq_sorted!(left, right, "custom message");
+    }
+}
+
+mod assert_matches {
+    use std::collections::HashMap;
+
+    const TEMPLATE: &str = "{\n    \"id\": \"{{[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}}}\",\n    \"name\": \"widget\",\n}";
+
+    #[test]
+    fn passes_when_placeholder_matches_uuid() {
+        let value: HashMap<_, _> = [
+            ("name", "widget"),
+            ("id", "67e55044-10b1-426f-9247-bb680e5fe0c8"),
+        ]
+        .into_iter()
+        .collect();
+        ::pretty_assertions_sorted::assert_matches_sorted!(value, TEMPLATE);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: `(value matches template)`: custom message")]
+    fn fails_when_placeholder_does_not_match() {
+        let value: HashMap<_, _> = [("name", "widget"), ("id", "not-a-uuid")]
+            .into_iter()
+            .collect();
+        ::pretty_assertions_sorted::assert_matches_sorted!(value, TEMPLATE, "custom message");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid template placeholder: unclosed group")]
+    fn panics_on_invalid_placeholder() {
+        ::pretty_assertions_sorted::assert_matches_sorted!(1, "{{(1}}");
+    }
+
+    #[test]
+    fn matches_escaped_braces() {
+        ::pretty_assertions_sorted::assert_matches_sorted!("{{1}}", "\"{{{{{{\\d}}}}\"");
+    }
+
+    #[test]
+    fn matches_large_values() {
+        let value: HashMap<_, _> = (0..500).map(|i| (format!("key{:03}", i), i)).collect();
+        ::pretty_assertions_sorted::assert_matches_sorted!(
+            value,
+            "{\n{{(    \"key\\d{3}\": \\d+,\n)*}}}"
+        );
+    }
+}
+
+mod assert_eq_within {
+    use std::collections::{HashMap, HashSet};
+
+    fn maps() -> (HashMap<&'static str, i32>, HashMap<&'static str, i32>) {
+        let left = [("a", 1), ("b", 2), ("c", 3), ("d", 4)]
+            .into_iter()
+            .collect();
+        let right = [("d", 4), ("c", 30), ("b", 2), ("a", 1), ("e", 5)]
+            .into_iter()
+            .collect();
+        (left, right)
+    }
+
+    #[test]
+    fn passes_with_fewer_differences_than_allowed() {
+        let (left, right) = maps();
+        ::pretty_assertions_sorted::assert_eq_sorted_within!(left, right, 3);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "2 differences, at most 1 allowed:\n  [\"c\"]: 3 != 30\n  [\"e\"]: <missing> != 5"
+    )]
+    fn fails_listing_all_differences() {
+        let (left, right) = maps();
+        ::pretty_assertions_sorted::assert_eq_sorted_within!(left, right, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 differences, at most 0 allowed:\n  [0]: <missing> != 1\n")]
+    fn counts_an_element_inserted_into_a_set_once() {
+        let left: HashSet<_> = (2..=10).collect();
+        let right: HashSet<_> = (1..=10).collect();
+        ::pretty_assertions_sorted::assert_eq_sorted_within!(left, right, 1);
+        ::pretty_assertions_sorted::assert_eq_sorted_within!(left, right, 0);
+    }
+}
+
+mod panic_location {
+    use std::cell::RefCell;
+    use std::panic;
+    use std::sync::Once;
+
+    thread_local! {
+        static LOCATION: RefCell<Option<(String, u32)>> = const { RefCell::new(None) };
+    }
+
+    /// Returns the location that `f` panicked at.
+    fn panic_location(f: impl FnOnce() + panic::UnwindSafe) -> Option<(String, u32)> {
+        static RECORD_LOCATIONS: Once = Once::new();
+        RECORD_LOCATIONS.call_once(|| {
+            let default_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let location = info
+                    .location()
+                    .map(|location| (location.file().to_string(), location.line()));
+                LOCATION.with(|cell| *cell.borrow_mut() = location);
+                default_hook(info)
+            }));
+        });
+
+        assert!(panic::catch_unwind(f).is_err());
+        LOCATION.with(|cell| cell.borrow_mut().take())
+    }
+
+    #[test]
+    fn reports_the_assertion_site() {
+        let location = panic_location(|| ::pretty_assertions_sorted::assert_eq_sorted!(5, 6));
+        assert_eq!(location, Some((file!().to_string(), line!() - 1)));
+
+        let location = panic_location(|| ::pretty_assertions_sorted::assert_ne_sorted!(5, 5));
+        assert_eq!(location, Some((file!().to_string(), line!() - 1)));
+
+        let expected = ::pretty_assertions_sorted::sorted_value(&5);
+        let location =
+            panic_location(|| ::pretty_assertions_sorted::assert_value_eq_sorted!(expected, 6));
+        assert_eq!(location, Some((file!().to_string(), line!() - 1)));
+
+        let location =
+            panic_location(|| ::pretty_assertions_sorted::assert_matches_sorted!(5, "6"));
+        assert_eq!(location, Some((file!().to_string(), line!() - 1)));
+    }
+
+    #[test]
+    fn reports_the_assertion_site_for_unparseable_values() {
+        struct Unparseable;
+
+        impl std::fmt::Debug for Unparseable {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("Object {\"a\": Number(0)}")
+            }
+        }
+
+        let location =
+            panic_location(|| ::pretty_assertions_sorted::assert_eq_sorted!(Unparseable, 1));
+        assert_eq!(location, Some((file!().to_string(), line!() - 1)));
+    }
+}
+
+mod formatting {
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::panic::{self, AssertUnwindSafe};
+
+    struct Counted<'a> {
+        calls: &'a Cell<usize>,
+        map: HashMap<&'static str, u8>,
+    }
+
+    impl fmt::Debug for Counted<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.calls.set(self.calls.get() + 1);
+            fmt::Debug::fmt(&self.map, f)
+        }
+    }
+
+    /// Returns how often the Debug implementation of each side was called by `assert`.
+    fn debug_calls(
+        left: &[(&'static str, u8)],
+        right: &[(&'static str, u8)],
+        assert: impl FnOnce(&Counted, &Counted),
+    ) -> (usize, usize) {
+        let (left_calls, right_calls) = (Cell::new(0), Cell::new(0));
+        let left = Counted {
+            calls: &left_calls,
+            map: left.iter().copied().collect(),
+        };
+        let right = Counted {
+            calls: &right_calls,
+            map: right.iter().copied().collect(),
+        };
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| assert(&left, &right)));
+        (left_calls.get(), right_calls.get())
+    }
+
+    #[test]
+    fn formats_each_side_once() {
+        let (map, other) = (&[("a", 1), ("b", 2)], &[("b", 2), ("c", 3)]);
+        let assert_eq = |left: &Counted, right: &Counted| {
+            ::pretty_assertions_sorted::assert_eq_sorted!(left, right)
+        };
+        let assert_ne = |left: &Counted, right: &Counted| {
+            ::pretty_assertions_sorted::assert_ne_sorted!(left, right)
+        };
+        assert_eq!(debug_calls(map, map, assert_eq), (1, 1));
+        assert_eq!(debug_calls(map, other, assert_eq), (1, 1));
+        assert_eq!(debug_calls(map, map, assert_ne), (1, 1));
+        assert_eq!(debug_calls(map, other, assert_ne), (1, 1));
+    }
 }